@@ -0,0 +1,96 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use anyhow::{anyhow, Context, Result};
+
+use crate::config;
+use crate::dryrun;
+
+/// A package file kept for reinstalling into a box.
+#[derive(Debug, Clone)]
+pub struct Archived {
+    pub name: String,
+    pub version: String,
+    pub path: PathBuf,
+    modified: SystemTime,
+}
+
+/// Path components come from package metadata; keep them to a single, harmless segment.
+fn component(s: &str) -> String {
+    let c: String = s.chars().map(|ch| if ch == '/' || ch == '\0' { '_' } else { ch }).collect();
+    if c.is_empty() || c == "." || c == ".." { "_".to_string() } else { c }
+}
+
+/// Copy an installed package file into the archive for `box_name`.
+pub fn store(box_name: &str, name: &str, version: &str, file: &Path) -> Result<PathBuf> {
+    let dir = config::archive_dir().join(component(box_name)).join(component(name)).join(component(version));
+    let file_name = file.file_name().ok_or_else(|| anyhow!("{} has no file name", file.display()))?;
+    let dest = dir.join(file_name);
+    // Reinstalling from the archive itself must not copy the file onto itself
+    if fs::canonicalize(file).ok() == fs::canonicalize(&dest).ok() && dest.exists() {
+        return Ok(dest);
+    }
+    dryrun::create_dir_all(&dir).with_context(|| format!("creating {}", dir.display()))?;
+    // One file per version: a rebuilt package with the same version replaces the old copy
+    if let Ok(rd) = fs::read_dir(&dir) {
+        for e in rd.filter_map(|e| e.ok()) { let _ = dryrun::remove_file(&e.path()); }
+    }
+    dryrun::copy(file, &dest).with_context(|| format!("copying {} to {}", file.display(), dest.display()))?;
+    Ok(dest)
+}
+
+/// Every archived package file for `box_name`.
+pub fn list(box_name: &str) -> Vec<Archived> {
+    let mut out = Vec::new();
+    let root = config::archive_dir().join(component(box_name));
+    for pkg in read_dirs(&root) {
+        for ver in read_dirs(&pkg) {
+            let Ok(rd) = fs::read_dir(&ver) else { continue; };
+            for f in rd.filter_map(|e| e.ok()).map(|e| e.path()).filter(|p| p.is_file()) {
+                let modified = fs::metadata(&f).and_then(|m| m.modified()).unwrap_or(SystemTime::UNIX_EPOCH);
+                out.push(Archived { name: file_name(&pkg), version: file_name(&ver), path: f, modified });
+            }
+        }
+    }
+    out.sort_by(|a, b| a.name.cmp(&b.name).then(a.modified.cmp(&b.modified)));
+    out
+}
+
+/// The most recently archived file of each package in `box_name`.
+pub fn latest_per_package(box_name: &str) -> Vec<Archived> {
+    let mut out: Vec<Archived> = Vec::new();
+    for a in list(box_name) {
+        // list() is sorted by name, then age; the last entry of each name wins
+        match out.last_mut() {
+            Some(last) if last.name == a.name => *last = a,
+            _ => out.push(a),
+        }
+    }
+    out
+}
+
+/// The archived file for `pkg`: the given version, or the most recently archived one.
+pub fn find(box_name: &str, pkg: &str, version: Option<&str>) -> Option<Archived> {
+    list(box_name)
+        .into_iter()
+        .filter(|a| a.name == pkg && version.map(|v| a.version == v).unwrap_or(true))
+        .max_by_key(|a| a.modified)
+}
+
+/// Drop every archived version of `pkg` for `box_name`.
+pub fn forget(box_name: &str, pkg: &str) -> Result<bool> {
+    let dir = config::archive_dir().join(component(box_name)).join(component(pkg));
+    if !dir.exists() { return Ok(false); }
+    dryrun::remove_dir_all(&dir).with_context(|| format!("removing {}", dir.display()))?;
+    Ok(true)
+}
+
+fn read_dirs(dir: &Path) -> Vec<PathBuf> {
+    let Ok(rd) = fs::read_dir(dir) else { return vec![]; };
+    rd.filter_map(|e| e.ok()).map(|e| e.path()).filter(|p| p.is_dir()).collect()
+}
+
+fn file_name(p: &Path) -> String {
+    p.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default()
+}
@@ -8,16 +8,25 @@ use crate::distro::Family as BoxFamily;
 use crate::pm;
 use crate::config;
 use crate::desktop;
+use crate::provision;
+use crate::hostfiles;
+use crate::pkgops;
 use std::io::IsTerminal;
 use crate::pkgdetect::{detect_package_format, PackageFormat};
-use std::path::PathBuf as StdPathBuf;
+use crate::pkgmeta;
+use crate::verify;
+use crate::archive;
+use crate::quote;
+use crate::repos;
+use crate::dryrun;
+use crate::journal;
 
 #[derive(Parser, Debug)]
 #[command(name = "pkgbridge", version, about = "Install native packages into Distrobox containers and export CLIs/desktop apps to the host.")]
 pub struct Cli {
     #[command(subcommand)]
     command: Commands,
-    /// Increase output verbosity
+    /// Print every host file change, box change and export instead of performing it
     #[arg(long, global = true, default_value_t = false)]
     dry_run: bool,
     /// Force a specific box by name
@@ -44,6 +53,24 @@ pub struct Cli {
     /// Log level
     #[arg(long, value_enum, global = true)]
     log_level: Option<LogLevel>,
+    /// Refuse packages without a good signature from a trusted key (see `keys`)
+    #[arg(long, global = true, default_value_t = false)]
+    require_signed: bool,
+    /// Commit the box to an image before installing and restore it if the install fails
+    #[arg(long, global = true, default_value_t = false)]
+    checkpoint: bool,
+    /// Read the box user's initial password from this file descriptor
+    #[arg(long, global = true, value_name = "FD", conflicts_with_all = ["password_stdin", "password_file"])]
+    password_fd: Option<u32>,
+    /// Read the box user's initial password from the first line of stdin
+    #[arg(long, global = true, default_value_t = false, conflicts_with = "password_file")]
+    password_stdin: bool,
+    /// Read the box user's initial password from a file only you can read (mode 0600)
+    #[arg(long, global = true, value_name = "PATH")]
+    password_file: Option<PathBuf>,
+    /// Expected SHA-256 of the package (otherwise taken from a SHA256SUMS file next to it, if any)
+    #[arg(long, global = true, value_name = "HEX")]
+    sha256: Option<String>,
 }
 
 #[derive(Subcommand, Debug)]
@@ -55,7 +82,7 @@ enum Commands {
     /// Re-export binaries/desktop entries for an installed package.
     Export(PkgArg),
     /// Uninstall a package from a box and remove exports.
-    Uninstall(PkgArg),
+    Uninstall(UninstallArgs),
     /// List boxes discovered via distrobox
     List(ListArgs),
     /// Check environment (distrobox, container runtime, XDG dirs)
@@ -64,6 +91,40 @@ enum Commands {
     Pm { #[command(subcommand)] cmd: PmCmd },
     /// Desktop integration (MIME/desktop file)
     Desktop { #[command(subcommand)] cmd: DesktopCmd },
+    /// Box lifecycle (create/start/stop/rm/upgrade/enter)
+    Box { #[command(subcommand)] cmd: BoxCmd },
+    /// Upgrade all packages in one box (--container) or every box (--all)
+    Upgrade(UpgradeArgs),
+    /// Search repositories of every default box (or --container) for a package
+    Search(SearchArgs),
+    /// Install a repository package by name into a box and export it
+    Add(PkgArg),
+    /// Show where a host command comes from (box, package, version, export method)
+    Which { cmd: String },
+    /// Show package metadata and exported items for a package inside a box
+    Info { box_name: String, pkg: String },
+    /// Keep a package in a box at its installed version (apt-mark hold, dnf versionlock, zypper addlock, IgnorePkg)
+    Hold { box_name: String, pkg: String },
+    /// Release a package held with `hold`
+    Unhold { box_name: String, pkg: String },
+    /// Inspect a .deb or .rpm on the host without entering or creating any box
+    Inspect(FileArg),
+    /// Manage keys trusted for package signature verification
+    Keys { #[command(subcommand)] cmd: KeysCmd },
+    /// Reinstall a package file archived for a box by an earlier install
+    Reinstall {
+        box_name: String,
+        pkg: String,
+        /// Archived version to install (default: the most recently installed one)
+        #[arg(long)]
+        version: Option<String>,
+    },
+    /// Remove staged package files and package-manager caches inside boxes (all, or --container)
+    Clean,
+    /// Manage third-party repositories in a box (--container, or the default box of --family)
+    Repo { #[command(subcommand)] cmd: RepoCmd },
+    /// Revert pkgbridge's PATH setup in ~/.profile (or fish conf.d) and its mimeapps.list defaults
+    UndoHostChanges,
 }
 
 #[derive(Args, Debug, Clone)]
@@ -82,12 +143,34 @@ pub struct ListArgs {
 #[derive(Debug, Clone, Copy, ValueEnum)]
 pub enum ListTarget { Boxes }
 
+#[derive(Args, Debug, Clone)]
+pub struct UpgradeArgs {
+    /// Upgrade every discovered box
+    #[arg(long, default_value_t = false)]
+    all: bool,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct SearchArgs {
+    /// Search term passed to each box's package manager
+    term: String,
+}
+
 #[derive(Args, Debug, Clone)]
 pub struct PkgArg {
     /// Package name inside the container
     pkg: String,
 }
 
+#[derive(Args, Debug, Clone)]
+pub struct UninstallArgs {
+    /// Package name inside the container
+    pkg: String,
+    /// Also remove dependencies that nothing else needs any more
+    #[arg(long, default_value_t = false)]
+    autoremove: bool,
+}
+
 #[derive(Subcommand, Debug, Clone)]
 pub enum PmCmd {
     /// Set default box for a distro family
@@ -100,23 +183,106 @@ pub enum PmCmd {
     Snapshot,
     /// Detect changes since snapshot and export new/updated apps
     PostTransaction,
+    /// List package-manager shims written by pkgbridge and their status
+    ListShims,
+    /// Rewrite pkgbridge-owned shims for the current defaults (never touches other files)
+    Regenerate {
+        /// Also overwrite pkgbridge shims that were edited by hand
+        #[arg(long, default_value_t = false)]
+        force: bool,
+    },
+    /// Check that shims are present, unmodified and bound to the current defaults
+    Verify,
+    /// Remove package-manager shims written by pkgbridge
+    RemoveShims {
+        /// Also remove pkgbridge shims that were edited by hand
+        #[arg(long, default_value_t = false)]
+        force: bool,
+    },
+    /// Run a package manager in its family's default box (what the shims call)
+    Exec {
+        /// Manager (apt, dnf, pacman, apk, ...) or family (debian, fedora, ...)
+        manager: String,
+        /// Arguments for the package manager, after `--`
+        #[arg(last = true)]
+        args: Vec<String>,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum BoxCmd {
+    /// Create a box for --family (or from --create-image) and provision it
+    Create { name: Option<String> },
+    /// Start a box
+    Start { name: String },
+    /// Stop a box
+    Stop { name: String },
+    /// Remove a box along with its exports and package-manager shims
+    Rm { name: String },
+    /// Run the family's full upgrade inside a box and refresh exports
+    Upgrade { name: String },
+    /// Open a shell inside a box
+    Enter { name: String },
+    /// Run provisioning steps from the family template that have not been applied yet
+    Provision { name: String },
+    /// Recreate a box from its image, re-provision it and reinstall its archived packages
+    Rebuild { name: String },
+    /// Let your user run the box's package managers through sudo without a password
+    Nopasswd {
+        name: String,
+        /// Remove the rule instead
+        #[arg(long, default_value_t = false)]
+        revoke: bool,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum KeysCmd {
+    /// Import trusted keys from a key file (armored or binary)
+    Add { file: PathBuf },
+    /// List trusted keys
+    List,
+    /// Remove a trusted key by fingerprint
+    Rm { fingerprint: String },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum RepoCmd {
+    /// Add a repository: ppa:user/name, copr:owner/project, obs:project[/platform], a URL or a `deb ...` line
+    Add {
+        spec: String,
+        /// Signing key: a host file, a URL fetched inside the box, or (Arch) a key fingerprint
+        #[arg(long)]
+        key: Option<String>,
+        /// Name for the repository (derived from the spec by default; pacman needs the repository's own name)
+        #[arg(long)]
+        name: Option<String>,
+    },
+    /// List repositories added to the box with `repo add`
+    List,
+    /// Remove a repository added with `repo add`, by name or spec
+    Rm { repo: String },
 }
 
 #[derive(Subcommand, Debug, Clone)]
 pub enum DesktopCmd {
     /// Install desktop file and MIME associations
     Install,
-    /// Remove desktop file and leave MIME defaults unchanged
+    /// Remove desktop file and restore the MIME defaults it replaced
     Uninstall,
 }
 
 pub fn run() -> Result<()> {
     let cli = Cli::parse();
     init_logger(cli.log_level);
-    maybe_first_run_prompt();
+    if cli.dry_run { dryrun::enable(); }
+    journal::set_command(std::iter::once("pkgbridge".to_string()).chain(std::env::args().skip(1)));
+    // Shims run through `pm exec`; onboarding there would hijack the user's package-manager call
+    // and under --dry-run it would write shims and exports for real
+    if !cli.dry_run && !matches!(cli.command, Commands::Pm { cmd: PmCmd::Exec { .. } }) { maybe_first_run_prompt()?; }
 
     match &cli.command {
-        Commands::Open(arg) | Commands::Install(arg) => install_like(arg.clone(), &cli),
+        Commands::Open(arg) | Commands::Install(arg) => install_like(&arg.file, &cli, cli.container.as_deref(), true),
         Commands::Export(arg) => export_pkg(&cli, arg.clone()),
         Commands::Uninstall(arg) => uninstall_pkg(&cli, arg.clone()),
         Commands::List(args) => match args.target {
@@ -127,7 +293,7 @@ pub fn run() -> Result<()> {
                 } else {
                     println!("NAME\tFAMILY\tRUNTIME\tIMAGE");
                     for b in boxes {
-                        let fam = match distro::classify_box_family(&b.name) { Ok(f) => format_family(f), Err(_) => "?".into() };
+                        let fam = match distro::classify_box_family(&b.name) { Ok(f) => format_family(f), Err(_) => "?" };
                         println!("{}\t{}\t{}\t{}", b.name, fam, b.runtime, b.image.unwrap_or_default());
                     }
                 }
@@ -135,62 +301,117 @@ pub fn run() -> Result<()> {
             }
         },
         Commands::Doctor => doctor(),
-        Commands::Pm { cmd } => pm_cmd(cmd.clone()),
-        Commands::Desktop { cmd } => desktop_cmd(cmd.clone(), cli.dry_run),
+        Commands::Pm { cmd } => pm_cmd(cmd.clone(), &cli),
+        Commands::Desktop { cmd } => desktop_cmd(cmd.clone()),
+        Commands::Box { cmd } => box_cmd(cmd.clone(), &cli),
+        Commands::Upgrade(args) => upgrade_boxes(args, &cli),
+        Commands::Search(args) => search(args, &cli),
+        Commands::Add(arg) => add_pkg(&cli, arg.clone()),
+        Commands::Which { cmd } => which_cmd(cmd),
+        Commands::Info { box_name, pkg } => info_pkg(box_name, pkg),
+        Commands::Hold { box_name, pkg } => hold_pkg(box_name, pkg),
+        Commands::Unhold { box_name, pkg } => unhold_pkg(box_name, pkg),
+        Commands::Inspect(arg) => inspect_file(&arg.file, &cli),
+        Commands::Keys { cmd } => keys_cmd(cmd.clone()),
+        Commands::Repo { cmd } => repo_cmd(cmd.clone(), &cli),
+        Commands::Reinstall { box_name, pkg, version } => reinstall(box_name, pkg, version.as_deref(), &cli),
+        Commands::Clean => clean(&cli),
+        Commands::UndoHostChanges => undo_host_changes(),
     }
 }
 
-fn install_like(arg: FileArg, cli: &Cli) -> Result<()> {
-    let path = arg.file;
+/// Install a package file into `container`, or the box `select_or_create` picks when it is `None`.
+/// With `check_holds`, a package held in that box is refused.
+fn install_like(path: &std::path::Path, cli: &Cli, container: Option<&str>, check_holds: bool) -> Result<()> {
     if !path.exists() {
         return Err(anyhow!("file does not exist: {}", path.display()));
     }
 
-    let fmt = detect_package_format(&path).context("detecting package format")?;
+    let fmt = detect_package_format(path).context("detecting package format")?;
     println!("Detected format: {}", match fmt { PackageFormat::Deb => "deb", PackageFormat::Rpm => "rpm"});
+    check_signature(path, fmt, cli.require_signed || config::load_config()?.require_signed)?;
+    let host_sha = check_sha256(path, cli.sha256.as_deref())?;
+    // Read once; the checks below and the archive all work from this copy
+    let meta = pkgmeta::read_package(path, fmt)?;
+    // Before a box is picked, so a refused package never leaves a freshly created box behind
+    gate_scripts(path, &meta)?;
     let containers = distro::discover_boxes().unwrap_or_default();
-    let selected = select_or_create(&containers, &fmt, cli)?;
+    let selected = select_or_create(&containers, &fmt, cli, container)?;
     println!("Selected box: {} (family: {})", selected.name, format_family(selected.family));
     println!("Plan: install {} inside '{}'", path.display(), selected.name);
+    if check_holds && held_pkgs(&selected.name)?.contains(&meta.name) {
+        return Err(anyhow!("'{}' is held in '{}'; run `pkgbridge unhold {} {}` first", meta.name, selected.name, selected.name, meta.name));
+    }
     if cli.dry_run {
         println!("--dry-run: stopping before any installation/export work.");
         return Ok(());
     }
-    // If non-interactive and a password seed is provided, set it before any container entry
     let interactive = std::io::stdin().is_terminal() && std::io::stdout().is_terminal();
-    if !interactive {
-        if let Ok(pw) = std::env::var("PKGBRIDGE_INIT_PASSWORD") {
-            preseed_password_root(&selected.name, &pw).ok();
-        }
+    // If a password seed is provided, set it before the install so sudo can use it
+    if let Some(pw) = read_password(cli)? {
+        preseed_password(&selected.name, &pw)?;
+    } else if std::env::var_os("PKGBRIDGE_INIT_PASSWORD").is_some() {
+        eprintln!("Warning: PKGBRIDGE_INIT_PASSWORD is ignored; use --password-fd, --password-stdin or --password-file");
     }
-    // Copy the package into the container to a temp path
-    let in_box_path = distro::copy_into_box(&selected.name, &path).context("copying package into container")?;
-    // Verify copy size to avoid corrupted installs due to TTY/pipe issues
-    if let Ok(meta) = std::fs::metadata(&path) {
-        let host_sz = meta.len();
-        let q = shell_escape::escape(std::borrow::Cow::from(in_box_path.clone()));
-        let cmd = format!("stat -c %s {} 2>/dev/null || wc -c < {} 2>/dev/null", q, q);
-        if let Ok(out) = distro::enter_capture(&selected.name, &cmd, false) {
-            if out.status.success() {
-                if let Ok(s) = String::from_utf8(out.stdout) {
-                    if let Some(tok) = s.split_whitespace().next() {
-                        if let Ok(n) = tok.trim().parse::<u64>() {
-                            if n != host_sz { return Err(anyhow!("copied file size mismatch inside container: expected {} bytes, got {} (path: {})", host_sz, n, in_box_path)); }
-                        }
-                    }
-                }
+    if !cli.checkpoint {
+        let (bins, apps) = install_into_box(path, fmt, &selected.name, cli, &host_sha, interactive)?;
+        return finish_install(path, &meta, &selected.name, &bins, &apps, cli);
+    }
+    // Checkpoint mode: commit the container first and restore it if anything below fails
+    let before = list_installed_pkgs(&selected.name, Some(selected.family))?;
+    let checkpoint = distro::checkpoint_box(&selected.name).context("checkpointing box before install")?;
+    println!("Checkpointed '{}' as {}", selected.name, checkpoint.image);
+    let res = install_into_box(path, fmt, &selected.name, cli, &host_sha, interactive)
+        .and_then(|items| check_install(&meta, &selected.name, selected.family, &before).map(|_| items));
+    match res {
+        Ok((bins, apps)) => {
+            if let Err(e) = distro::drop_checkpoint(&checkpoint) {
+                eprintln!("Warning: could not remove checkpoint {}: {:#}", checkpoint.image, e);
             }
+            finish_install(path, &meta, &selected.name, &bins, &apps, cli)
+        }
+        Err(e) => {
+            println!("Install failed; restoring '{}' from {}...", selected.name, checkpoint.image);
+            eprintln!("Warning: options given to `distrobox create` with --additional-flags or --unshare-* are not carried over to the restored box");
+            distro::restore_checkpoint(&checkpoint).context("restoring box from checkpoint")?;
+            let mut st = config::load_state()?;
+            if st.staged.remove(&selected.name).is_some() { config::save_state(&st)?; }
+            Err(e.context(format!("install rolled back; '{}' restored from {}", selected.name, checkpoint.image)))
         }
     }
+}
+
+/// Package checks run in checkpoint mode: the package is installed and nothing else was removed.
+fn check_install(meta: &pkgmeta::PackageMeta, box_name: &str, fam: BoxFamily, before: &[String]) -> Result<()> {
+    let after = list_installed_pkgs(box_name, Some(fam))?;
+    let name_of = |l: &String| l.split('\t').next().unwrap_or("").to_string();
+    let after_names: std::collections::HashSet<String> = after.iter().map(name_of).collect();
+    let removed: Vec<String> = before.iter().map(name_of).filter(|n| !after_names.contains(n)).collect();
+    if !removed.is_empty() {
+        return Err(anyhow!("install removed {} package(s): {}", removed.len(), removed.join(", ")));
+    }
+    if !after_names.contains(&meta.name) {
+        return Err(anyhow!("package '{}' is not installed after the install command", meta.name));
+    }
+    Ok(())
+}
+
+/// Copy, verify and install the package inside the box; returns the bins/apps to export.
+fn install_into_box(path: &std::path::Path, fmt: PackageFormat, box_name: &str, cli: &Cli, host_sha: &str, interactive: bool) -> Result<(Vec<String>, Vec<String>)> {
+    // Copy the package into the container to a temp path
+    let in_box_path = distro::copy_into_box(box_name, path).context("copying package into container")?;
+    track_staged(box_name, &in_box_path, true)?;
+    // Verify the copy to avoid corrupted installs due to TTY/pipe issues
+    verify_copy(box_name, path, &in_box_path, host_sha)?;
     // Pre-scan contents to identify bins and desktop files
-    let (mut bins, mut apps) = prescan_package(&selected.name, &fmt, &in_box_path)?;
+    let (mut bins, mut apps) = prescan_package(box_name, &fmt, &in_box_path)?;
     if !cli.bin.is_empty() { bins = cli.bin.clone(); }
     if !cli.app.is_empty() { apps = cli.app.clone(); }
     // Build both user and root install commands. Prefer user+sudo in interactive sessions
     // to forward password prompts; fallback to root if needed.
     let user_cmd = build_install_cmd_user(&fmt, &in_box_path);
     let root_cmd = build_install_cmd_root(&fmt, &in_box_path);
-    println!("Installing inside box '{}'...", selected.name);
+    println!("Installing inside box '{}'...", box_name);
 
     // Interactive or non-interactive execution depending on TTY
 
@@ -198,12 +419,12 @@ fn install_like(arg: FileArg, cli: &Cli) -> Result<()> {
     let ok = if interactive {
         // 1) Try as normal user (sudo/doas will prompt interactively)
         log::debug!("install (user) cmd: {}", user_cmd);
-        match distro::enter_status_inherit(&selected.name, &user_cmd, false) {
+        match distro::enter_status_inherit(box_name, &user_cmd, false) {
             Ok(true) => true,
             _ => {
                 // 2) Fallback to root (no prompts)
                 log::debug!("install (root fallback) cmd: {}", root_cmd);
-                match distro::enter_status_inherit(&selected.name, &root_cmd, true) {
+                match distro::enter_status_inherit(box_name, &root_cmd, true) {
                     Ok(true) => true,
                     Ok(false) => false,
                     Err(_) => false,
@@ -213,14 +434,11 @@ fn install_like(arg: FileArg, cli: &Cli) -> Result<()> {
     } else {
         // Non-interactive: try root first, then user without prompts
         log::debug!("install (root, non-interactive) cmd: {}", root_cmd);
-        match distro::enter_status(&selected.name, &root_cmd, true) {
+        match distro::enter_status(box_name, &root_cmd, true) {
             Ok(true) => true,
             _ => {
                 log::debug!("install (user, non-interactive) fallback cmd: {}", user_cmd);
-                match distro::enter_status(&selected.name, &user_cmd, false) {
-                    Ok(true) => true,
-                    _ => false,
-                }
+                matches!(distro::enter_status(box_name, &user_cmd, false), Ok(true))
             }
         }
     };
@@ -229,22 +447,173 @@ fn install_like(arg: FileArg, cli: &Cli) -> Result<()> {
         // Re-run in non-interactive mode to capture error details
         // Capture diagnostics from both paths to provide helpful output
         let mut details = String::new();
-        let diag_root = distro::enter_capture(&selected.name, &format!("{} 2>&1 || true", root_cmd), true);
+        let diag_root = distro::enter_capture(box_name, &format!("{} 2>&1 || true", root_cmd), true);
         if let Ok(out) = diag_root { details.push_str(&String::from_utf8_lossy(&out.stdout)); details.push_str(&String::from_utf8_lossy(&out.stderr)); }
-        let diag_user = distro::enter_capture(&selected.name, &format!("{} 2>&1 || true", user_cmd), false);
+        let diag_user = distro::enter_capture(box_name, &format!("{} 2>&1 || true", user_cmd), false);
         if let Ok(out) = diag_user { details.push_str(&String::from_utf8_lossy(&out.stdout)); details.push_str(&String::from_utf8_lossy(&out.stderr)); }
         return Err(anyhow!("installation command failed inside container. Details:\n{}", details.trim()));
     }
     println!("Install completed.");
+    if let Err(e) = release_staged(box_name, &in_box_path) {
+        eprintln!("Warning: could not clean up {} in '{}': {:#}", in_box_path, box_name, e);
+    }
+    Ok((bins, apps))
+}
+
+/// Archive the installed package for reinstalls and export its items.
+fn finish_install(path: &std::path::Path, meta: &pkgmeta::PackageMeta, box_name: &str, bins: &[String], apps: &[String], cli: &Cli) -> Result<()> {
+    match archive::store(box_name, &meta.name, &meta.version, path) {
+        Ok(p) => log::debug!("archived {} as {}", path.display(), p.display()),
+        Err(e) => eprintln!("Warning: could not archive {} for reinstall: {:#}", path.display(), e),
+    }
     if !cli.no_export {
-        export_items(&selected.name, &bins, &apps)?;
-        notify(&format!("Installed in {}", selected.name), &format!("Exported {} bins, {} apps", bins.len(), apps.len()));
+        export_items(box_name, bins, apps)?;
+        notify(&format!("Installed in {}", box_name), &format!("Exported {} bins, {} apps", bins.len(), apps.len()));
     } else {
         println!("--no-export: skipping export stage");
     }
     Ok(())
 }
 
+/// Hash the package on the host and compare it with the expected checksum, if one was given.
+fn check_sha256(path: &std::path::Path, given: Option<&str>) -> Result<String> {
+    let actual = verify::sha256_file(path)?;
+    match verify::expected_sha256(path, given)? {
+        Some((expected, _)) if expected != actual => Err(anyhow!(
+            "checksum mismatch for {}: expected {}, got {} (corrupted or tampered download?)",
+            path.display(), expected, actual
+        )),
+        Some((_, source)) => {
+            println!("SHA-256: {} (matches {})", actual, source);
+            Ok(actual)
+        }
+        None => {
+            log::debug!("sha256 {}: {}", path.display(), actual);
+            Ok(actual)
+        }
+    }
+}
+
+/// Compare the copy inside the box with the host file: SHA-256 when `sha256sum` exists, else byte size.
+fn verify_copy(box_name: &str, host_path: &std::path::Path, in_box_path: &str, host_sha: &str) -> Result<()> {
+    let q = shell_escape::escape(std::borrow::Cow::from(in_box_path.to_string()));
+    let cmd = format!("if command -v sha256sum >/dev/null 2>&1; then echo sha256 $(sha256sum {q} | cut -d' ' -f1); else echo size $(stat -c %s {q} 2>/dev/null || wc -c < {q}); fi", q = q);
+    let out = distro::enter_capture(box_name, &cmd, false).context("checking copied package inside container")?;
+    let s = String::from_utf8_lossy(&out.stdout);
+    let mut toks = s.split_whitespace();
+    match (toks.next(), toks.next()) {
+        (Some("sha256"), Some(digest)) if digest == host_sha => Ok(()),
+        (Some("sha256"), Some(digest)) => Err(anyhow!("copied file checksum mismatch inside container: expected {}, got {} (path: {})", host_sha, digest, in_box_path)),
+        (Some("size"), Some(n)) => {
+            let host_sz = std::fs::metadata(host_path)?.len();
+            match n.parse::<u64>() {
+                Ok(n) if n == host_sz => {
+                    eprintln!("Warning: no sha256sum in '{}'; only the size of the copied package was checked", box_name);
+                    Ok(())
+                }
+                Ok(n) => Err(anyhow!("copied file size mismatch inside container: expected {} bytes, got {} (path: {})", host_sz, n, in_box_path)),
+                Err(_) => Err(anyhow!("could not read size of {} inside container", in_box_path)),
+            }
+        }
+        _ => Err(anyhow!("could not verify {} inside container", in_box_path)),
+    }
+}
+
+/// Record (or forget) a package file copied into a box so `clean` can find leftovers.
+fn track_staged(box_name: &str, in_box_path: &str, add: bool) -> Result<()> {
+    let mut st = config::load_state()?;
+    let files = st.staged.entry(box_name.to_string()).or_default();
+    files.retain(|f| f != in_box_path);
+    if add { files.push(in_box_path.to_string()); }
+    if files.is_empty() { st.staged.remove(box_name); }
+    config::save_state(&st)
+}
+
+/// After a successful install, delete the staged package or move it to the box's local cache.
+fn release_staged(box_name: &str, in_box_path: &str) -> Result<()> {
+    let q = shell_escape::escape(std::borrow::Cow::from(in_box_path.to_string()));
+    let cmd = if config::load_config()?.keep_packages {
+        format!("set -e; mkdir -p {dir}; mv -f {q} {dir}/", dir = distro::PACKAGE_CACHE_DIR, q = q)
+    } else {
+        format!("rm -f {}", q)
+    };
+    if !distro::enter_status_elevated(box_name, &cmd)? {
+        return Err(anyhow!("command failed: {}", cmd));
+    }
+    track_staged(box_name, in_box_path, false)
+}
+
+/// Verify the package signature; unsigned or unknown-key packages only fail when signatures are required.
+fn check_signature(path: &std::path::Path, fmt: PackageFormat, require: bool) -> Result<()> {
+    let status = match verify::verify_package(path, fmt) {
+        Ok(s) => s,
+        Err(e) if !require => {
+            log::warn!("could not verify signature: {:#}", e);
+            println!("Signature: not checked");
+            return Ok(());
+        }
+        Err(e) => return Err(e.context("verifying package signature")),
+    };
+    match status {
+        verify::SigStatus::Good { fingerprint, method } => {
+            println!("Signature: good {} from {}", method, fingerprint);
+            Ok(())
+        }
+        verify::SigStatus::Bad { method } => Err(anyhow!("BAD {} on {}; refusing to install", method, path.display())),
+        verify::SigStatus::Rejected { reason, method } => Err(anyhow!("{} on {} not accepted: {}; refusing to install", method, path.display(), reason)),
+        verify::SigStatus::UnknownKey { key_id, method } if require => {
+            Err(anyhow!("{} by untrusted key {}; trust it with 'pkgbridge keys add <keyfile>'", method, key_id))
+        }
+        verify::SigStatus::UnknownKey { key_id, method } => {
+            println!("Signature: {} by untrusted key {} (not required)", method, key_id);
+            Ok(())
+        }
+        verify::SigStatus::Unsigned if require => Err(anyhow!("{} is not signed and signatures are required", path.display())),
+        verify::SigStatus::Unsigned => {
+            println!("Signature: none (not required)");
+            Ok(())
+        }
+    }
+}
+
+/// Show what the package's maintainer scripts do and apply the configured `scripts` policy.
+fn gate_scripts(path: &std::path::Path, meta: &pkgmeta::PackageMeta) -> Result<()> {
+    use config::ScriptPolicy;
+    let policy = config::load_config()?.scripts;
+    let scripts = pkgmeta::executable_scripts(meta);
+    if scripts.is_empty() {
+        println!("Maintainer scripts: none");
+        return Ok(());
+    }
+    let names: Vec<String> = scripts.iter().map(|s| format!("{} ({} lines)", s.heading(), s.body.lines().count())).collect();
+    println!("Maintainer scripts (run as root inside the box): {}", names.join(", "));
+    for f in pkgmeta::script_findings(&scripts) { println!("  - {}", f); }
+    match policy {
+        ScriptPolicy::Allow => Ok(()),
+        ScriptPolicy::Deny => Err(anyhow!("package ships maintainer scripts and the 'scripts' policy is 'deny'; review with 'pkgbridge inspect {}'", path.display())),
+        ScriptPolicy::Prompt => {
+            if !(std::io::stdin().is_terminal() && std::io::stdout().is_terminal()) {
+                return Err(anyhow!("package ships maintainer scripts and the 'scripts' policy is 'prompt', but there is no terminal to confirm on"));
+            }
+            loop {
+                print!("Run these scripts? [y/N/s(how)] ");
+                use std::io::Write; let _ = std::io::stdout().flush();
+                let mut buf = String::new(); let _ = std::io::stdin().read_line(&mut buf);
+                match buf.trim().to_ascii_lowercase().as_str() {
+                    "y" | "yes" => return Ok(()),
+                    "s" | "show" => {
+                        for s in &scripts {
+                            println!("--- {} ---", s.heading());
+                            println!("{}", s.body.trim_end());
+                        }
+                    }
+                    _ => return Err(anyhow!("installation cancelled")),
+                }
+            }
+        }
+    }
+}
+
 fn doctor() -> Result<()> {
     println!("pkgbridge doctor:");
 
@@ -280,6 +649,8 @@ fn doctor() -> Result<()> {
     println!("- xdg-mime present: {}", yes_no(have_xdg_mime));
     let have_update_db = which::which("update-desktop-database").is_ok();
     println!("- update-desktop-database present: {}", yes_no(have_update_db));
+    let have_gpgv = which::which("gpgv").is_ok();
+    println!("- gpgv present (signature checks): {}", yes_no(have_gpgv));
 
     Ok(())
 }
@@ -311,7 +682,7 @@ fn is_writable(p: &PathBuf) -> bool {
     }
 }
 
-fn path_contains(dir: &PathBuf) -> bool {
+fn path_contains(dir: &std::path::Path) -> bool {
     std::env::var_os("PATH")
         .and_then(|v| v.into_string().ok())
         .map(|p| p.split(':').any(|s| s == dir.to_string_lossy()))
@@ -319,14 +690,22 @@ fn path_contains(dir: &PathBuf) -> bool {
 }
 
 #[derive(Debug, Clone, Copy, ValueEnum)]
-pub enum FamilyArg { Debian, Fedora, Opensuse, Arch }
+pub enum FamilyArg { Debian, Fedora, Opensuse, Arch, Alpine, Void, Gentoo }
 
 fn format_family(f: BoxFamily) -> &'static str {
-    match f { BoxFamily::Debian => "debian", BoxFamily::Fedora => "fedora", BoxFamily::OpenSuse => "opensuse", BoxFamily::Arch => "arch" }
+    pm::family_key(f)
 }
 
 fn to_family(arg: FamilyArg) -> BoxFamily {
-    match arg { FamilyArg::Debian => BoxFamily::Debian, FamilyArg::Fedora => BoxFamily::Fedora, FamilyArg::Opensuse => BoxFamily::OpenSuse, FamilyArg::Arch => BoxFamily::Arch }
+    match arg {
+        FamilyArg::Debian => BoxFamily::Debian,
+        FamilyArg::Fedora => BoxFamily::Fedora,
+        FamilyArg::Opensuse => BoxFamily::OpenSuse,
+        FamilyArg::Arch => BoxFamily::Arch,
+        FamilyArg::Alpine => BoxFamily::Alpine,
+        FamilyArg::Void => BoxFamily::Void,
+        FamilyArg::Gentoo => BoxFamily::Gentoo,
+    }
 }
 
 struct SelectedBox {
@@ -334,14 +713,14 @@ struct SelectedBox {
     family: BoxFamily,
 }
 
-fn select_or_create(boxes: &[distro::DistroBox], fmt: &PackageFormat, cli: &Cli) -> Result<SelectedBox> {
+fn select_or_create(boxes: &[distro::DistroBox], fmt: &PackageFormat, cli: &Cli, container: Option<&str>) -> Result<SelectedBox> {
     // If specific container requested, verify and classify
-    if let Some(ref name) = cli.container {
-        if !boxes.iter().any(|b| &b.name == name) {
+    if let Some(name) = container {
+        if !boxes.iter().any(|b| b.name == name) {
             return Err(anyhow!("container '{}' not found", name));
         }
         let fam = distro::classify_box_family(name).context("classifying requested container")?;
-        return Ok(SelectedBox { name: name.clone(), family: fam });
+        return Ok(SelectedBox { name: name.to_string(), family: fam });
     }
 
     // Desired families based on format or explicit family
@@ -386,10 +765,12 @@ fn select_or_create(boxes: &[distro::DistroBox], fmt: &PackageFormat, cli: &Cli)
     // None found: create interactively or via --create
     let fam = target_families[0];
     let (default_name, default_image) = default_box_for_family(fam);
-    let chosen_image = cli.create_image.clone().unwrap_or_else(|| default_image.to_string());
+    let chosen_image = cli.create_image.clone()
+        .or(provision::template_image(fam)?)
+        .unwrap_or_else(|| default_image.to_string());
     if cli.create {
         println!("No matching box found. Creating '{}' from '{}'...", default_name, chosen_image);
-        distro::create_box(default_name, &chosen_image)?;
+        provision::create_and_provision(default_name, &chosen_image, fam)?;
         return Ok(SelectedBox { name: default_name.to_string(), family: fam });
     }
     // If interactive TTY, offer to create automatically
@@ -401,7 +782,7 @@ fn select_or_create(boxes: &[distro::DistroBox], fmt: &PackageFormat, cli: &Cli)
         let mut buf = String::new(); let _ = std::io::stdin().read_line(&mut buf);
         let ans = buf.trim().to_ascii_lowercase();
         if ans.is_empty() || ans == "y" || ans == "yes" {
-            distro::create_box(default_name, &chosen_image)?;
+            provision::create_and_provision(default_name, &chosen_image, fam)?;
             return Ok(SelectedBox { name: default_name.to_string(), family: fam });
         }
     }
@@ -409,12 +790,46 @@ fn select_or_create(boxes: &[distro::DistroBox], fmt: &PackageFormat, cli: &Cli)
     Err(anyhow!("no matching box found; re-run with --create, pass --container/--family, or answer 'Y' when prompted"))
 }
 
+/// Describe the box `select_or_create` would pick, using only configured defaults and image names.
+fn preview_selection(boxes: &[distro::DistroBox], fmt: &PackageFormat, cli: &Cli) -> Result<String> {
+    if let Some(ref name) = cli.container {
+        return Ok(if boxes.iter().any(|b| &b.name == name) { format!("'{}' (--container)", name) } else { format!("none: container '{}' not found", name) });
+    }
+    let target_families: Vec<BoxFamily> = if let Some(fa) = cli.family {
+        vec![to_family(fa)]
+    } else {
+        match fmt { PackageFormat::Deb => vec![BoxFamily::Debian], PackageFormat::Rpm => vec![BoxFamily::Fedora, BoxFamily::OpenSuse] }
+    };
+    let defaults = pm::show_defaults()?;
+    let mut matches: Vec<(String, BoxFamily)> = Vec::new();
+    for b in boxes {
+        let fam = defaults.iter()
+            .find(|(_, bx)| *bx == &b.name)
+            .and_then(|(k, _)| pm::family_from_key(k))
+            .or_else(|| b.image.as_deref().and_then(distro::guess_family_from_image));
+        if let Some(fam) = fam.filter(|f| target_families.contains(f)) { matches.push((b.name.clone(), fam)); }
+    }
+    Ok(match matches.len() {
+        1 => format!("'{}' ({})", matches[0].0, format_family(matches[0].1)),
+        0 => {
+            let fam = target_families[0];
+            let (name, image) = default_box_for_family(fam);
+            let image = cli.create_image.clone().or(provision::template_image(fam)?).unwrap_or_else(|| image.to_string());
+            format!("none; would create '{}' from '{}'", name, image)
+        }
+        _ => format!("one of {} (prompted)", matches.iter().map(|(n, f)| format!("'{}' ({})", n, format_family(*f))).collect::<Vec<_>>().join(", ")),
+    })
+}
+
 fn default_box_for_family(f: BoxFamily) -> (&'static str, &'static str) {
     match f {
         BoxFamily::Debian => ("debian-stable", "docker.io/library/debian:stable"),
         BoxFamily::Fedora => ("fedora-latest", "registry.fedoraproject.org/fedora:latest"),
         BoxFamily::OpenSuse => ("opensuse-tumbleweed", "registry.opensuse.org/opensuse/tumbleweed:latest"),
         BoxFamily::Arch => ("arch", "docker.io/library/archlinux:latest"),
+        BoxFamily::Alpine => ("alpine-latest", "docker.io/library/alpine:latest"),
+        BoxFamily::Void => ("void", "ghcr.io/void-linux/void-glibc-full:latest"),
+        BoxFamily::Gentoo => ("gentoo", "docker.io/gentoo/stage3:latest"),
     }
 }
 
@@ -454,38 +869,17 @@ fn build_install_cmd_user(fmt: &PackageFormat, path: &str) -> String {
     let p = shell_escape::escape(std::borrow::Cow::from(path.to_string()));
     let inner = match fmt {
         PackageFormat::Deb => format!(
-            "set -e; if command -v apt-get >/dev/null; then apt-get -y update; elif command -v apt >/dev/null; then apt -y update; fi; dpkg -i {} || {{ if command -v apt-get >/dev/null; then apt-get -y -f install; elif command -v apt >/dev/null; then apt -y -f install; else true; fi; }}",
+            "set -e; if command -v apt-get >/dev/null; then $s apt-get -y update; elif command -v apt >/dev/null; then $s apt -y update; fi; $s dpkg -i {} || {{ if command -v apt-get >/dev/null; then $s apt-get -y -f install; elif command -v apt >/dev/null; then $s apt -y -f install; else true; fi; }}",
             p
         ),
         PackageFormat::Rpm => format!(
-            "set -e; if command -v dnf >/dev/null; then dnf -y install {}; elif command -v zypper >/dev/null; then zypper --non-interactive install {}; else rpm -i {}; fi",
+            "set -e; if command -v dnf >/dev/null; then $s dnf -y install {}; elif command -v zypper >/dev/null; then $s zypper --non-interactive install {}; else $s rpm -i {}; fi",
             p, p, p
         ),
     };
-    // Prefer sudo (passwordless or interactive), then doas, else run without elevation (may fail)
-    format!(
-        "set -e; if command -v sudo >/dev/null; then if sudo -n true >/dev/null 2>&1; then sudo sh -lc '{}' ; else sudo sh -lc '{}' ; fi; elif command -v doas >/dev/null; then doas sh -lc '{}' ; else sh -lc '{}' ; fi",
-        inner, inner, inner, inner
-    )
-}
-
-fn build_install_cmd_user_noninteractive(fmt: &PackageFormat, path: &str) -> String {
-    let p = shell_escape::escape(std::borrow::Cow::from(path.to_string()));
-    let inner = match fmt {
-        PackageFormat::Deb => format!(
-            "set -e; if command -v apt-get >/dev/null; then apt-get -y update; elif command -v apt >/dev/null; then apt -y update; fi; dpkg -i {} || {{ if command -v apt-get >/dev/null; then apt-get -y -f install; elif command -v apt >/dev/null; then apt -y -f install; else true; fi; }}",
-            p
-        ),
-        PackageFormat::Rpm => format!(
-            "set -e; if command -v dnf >/dev/null; then dnf -y install {}; elif command -v zypper >/dev/null; then zypper --non-interactive install {}; else rpm -i {}; fi",
-            p, p, p
-        ),
-    };
-    // Force non-interactive sudo so we can capture errors, even if it fails due to needing a password
-    format!(
-        "set -e; if command -v sudo >/dev/null; then sudo -n sh -lc '{}' ; elif command -v doas >/dev/null; then doas sh -lc '{}' ; else sh -lc '{}' ; fi",
-        inner, inner, inner
-    )
+    // Prefer sudo (passwordless or interactive), then doas, else run without elevation (may fail).
+    // Each manager call is elevated on its own so per-binary sudoers rules (`box nopasswd`) apply.
+    format!("if command -v sudo >/dev/null; then s=sudo; elif command -v doas >/dev/null; then s=doas; else s=; fi; {}", inner)
 }
 
 fn prescan_package(box_name: &str, fmt: &PackageFormat, in_box_path: &str) -> Result<(Vec<String>, Vec<String>)> {
@@ -564,13 +958,14 @@ fn export_items(box_name: &str, bins: &[String], apps: &[String]) -> Result<()>
             // Collision; copy with container suffix and rewrite Exec
             let in_path = format!("/usr/share/applications/{}", base);
             let out = distro::enter_capture(box_name, &format!("cat {}", shell_escape::escape(std::borrow::Cow::from(in_path.clone()))), false)?;
-            let mut content = String::from_utf8_lossy(&out.stdout).to_string();
+            let content = String::from_utf8_lossy(&out.stdout).to_string();
             // Rewrite Exec lines
             let mut new_lines = Vec::new();
             for line in content.lines() {
                 if line.starts_with("Exec=") && !line.contains("distrobox enter -n") {
                     let old = &line[5..];
-                    let replaced = format!("Exec=distrobox enter -n {} -- {}", box_name, old);
+                    // The original value is already valid Exec syntax (field codes included); only the prefix is new
+                    let replaced = format!("Exec={} {}", quote::desktop_exec(&["distrobox", "enter", "-n", box_name, "--"]), old);
                     new_lines.push(replaced);
                 } else {
                     new_lines.push(line.to_string());
@@ -578,8 +973,8 @@ fn export_items(box_name: &str, bins: &[String], apps: &[String]) -> Result<()>
             }
             let new_content = new_lines.join("\n");
             let alt_name = format!("{}.{}.desktop", base.trim_end_matches(".desktop"), box_name);
-            std::fs::create_dir_all(&apps_dir).ok();
-            std::fs::write(apps_dir.join(&alt_name), new_content)?;
+            dryrun::create_dir_all(&apps_dir).ok();
+            dryrun::write(&apps_dir.join(&alt_name), new_content)?;
             println!("App collision for '{}'; exported as '{}'", base, alt_name);
             continue;
         }
@@ -608,14 +1003,12 @@ fn host_apps_dir() -> std::path::PathBuf {
 
 fn write_simple_shim(dir: &std::path::Path, out_name: &str, box_name: &str, cmd_name: &str) -> Result<()> {
     let path = dir.join(out_name);
-    let content = format!("#!/usr/bin/env sh\nexec distrobox enter -n {} -- {} \"$@\"\n", box_name, cmd_name);
-    std::fs::create_dir_all(dir).ok();
-    std::fs::write(&path, content)?;
+    let content = quote::sh_template("#!/usr/bin/env sh\nexec distrobox enter -n {{box}} -- {{cmd}} \"$@\"\n", &[("box", box_name), ("cmd", cmd_name)]);
+    dryrun::create_dir_all(dir).ok();
+    dryrun::write(&path, content)?;
     #[cfg(unix)] {
         use std::os::unix::fs::PermissionsExt;
-        let mut perms = std::fs::metadata(&path)?.permissions();
-        perms.set_mode(0o755);
-        std::fs::set_permissions(&path, perms)?;
+        dryrun::set_permissions(&path, std::fs::Permissions::from_mode(0o755))?;
     }
     Ok(())
 }
@@ -629,29 +1022,45 @@ fn dbe_supports_container_flag() -> bool {
 }
 
 fn export_bin(box_name: &str, bin: &str) -> bool {
+    let target = host_bin_dir().join(bin);
+    let existed = target.exists();
+    let ok = run_export_bin(box_name, bin);
+    if ok { journal::record_outcome(&target, existed); }
+    ok
+}
+
+fn run_export_bin(box_name: &str, bin: &str) -> bool {
     let supports = dbe_supports_container_flag();
     if supports {
         // Try by name first, then fallback to absolute path
-        let status = std::process::Command::new("distrobox-export")
-            .args(["--container", box_name, "--bin", bin])
-            .status();
+        let status = dryrun::status(std::process::Command::new("distrobox-export").args(["--container", box_name, "--bin", bin]));
         if let Ok(s) = status { if s.success() { return true; } }
         let abs = format!("/usr/bin/{}", bin);
-        let status2 = std::process::Command::new("distrobox-export")
-            .args(["--container", box_name, "--bin", &abs])
-            .status();
-        return matches!(status2, Ok(s) if s.success());
+        let status2 = dryrun::status(std::process::Command::new("distrobox-export").args(["--container", box_name, "--bin", &abs]));
+        matches!(status2, Ok(s) if s.success())
     } else {
         // Older versions: run from inside container, requires absolute path
         let abs = format!("/usr/bin/{}", bin);
-        let status = std::process::Command::new("distrobox")
-            .args(["enter", "-n", box_name, "--", "distrobox-export", "--bin", &abs])
-            .status();
-        return matches!(status, Ok(s) if s.success());
+        let status = dryrun::status(std::process::Command::new("distrobox").args(["enter", "-n", box_name, "--", "distrobox-export", "--bin", &abs]));
+        matches!(status, Ok(s) if s.success())
     }
 }
 
 fn export_app(box_name: &str, app_spec: &str) -> bool {
+    let base = std::path::Path::new(app_spec).file_name().and_then(|s| s.to_str()).unwrap_or(app_spec).trim_end_matches(".desktop");
+    let target = exported_app_path(box_name, &format!("{}.desktop", base));
+    let existed = target.exists();
+    let ok = run_export_app(box_name, app_spec);
+    if ok { journal::record_outcome(&target, existed); }
+    ok
+}
+
+/// Where `distrobox-export --app` puts a box's desktop entry on the host.
+fn exported_app_path(box_name: &str, base: &str) -> PathBuf {
+    host_apps_dir().join(format!("{}-{}", box_name, base))
+}
+
+fn run_export_app(box_name: &str, app_spec: &str) -> bool {
     // Normalize: if caller passed only a basename ending with .desktop, use absolute path
     // Accepted by distrobox-export: app name (without .desktop) or absolute path to .desktop
     let normalized = if app_spec.contains('/') {
@@ -663,24 +1072,16 @@ fn export_app(box_name: &str, app_spec: &str) -> bool {
     };
     let supports = dbe_supports_container_flag();
     if supports {
-        let status = std::process::Command::new("distrobox-export")
-            .args(["--container", box_name, "--app", &normalized])
-            .status();
-        return matches!(status, Ok(s) if s.success());
+        let status = dryrun::status(std::process::Command::new("distrobox-export").args(["--container", box_name, "--app", &normalized]));
+        matches!(status, Ok(s) if s.success())
     } else {
-        let status = std::process::Command::new("distrobox")
-            .args(["enter", "-n", box_name, "--", "distrobox-export", "--app", &normalized])
-            .status();
-        return matches!(status, Ok(s) if s.success());
+        let status = dryrun::status(std::process::Command::new("distrobox").args(["enter", "-n", box_name, "--", "distrobox-export", "--app", &normalized]));
+        matches!(status, Ok(s) if s.success())
     }
 }
 
 fn scan_installed_pkg(box_name: &str, fam: BoxFamily, pkg: &str) -> Result<(Vec<String>, Vec<String>)> {
-    let cmd = match fam {
-        BoxFamily::Debian => format!("dpkg -L {}", shell_escape::escape(std::borrow::Cow::from(pkg.to_string()))),
-        BoxFamily::Fedora | BoxFamily::OpenSuse | BoxFamily::Arch => format!("rpm -ql {}", shell_escape::escape(std::borrow::Cow::from(pkg.to_string()))),
-    };
-    let out = distro::enter_capture(box_name, &cmd, false)?;
+    let out = distro::enter_capture(box_name, &pkgops::files_cmd(fam, pkg), false)?;
     let stdout = String::from_utf8_lossy(&out.stdout);
     let mut bins = Vec::new();
     let mut apps = Vec::new();
@@ -700,201 +1101,916 @@ fn export_pkg(cli: &Cli, arg: PkgArg) -> Result<()> {
     let (mut bins, mut apps) = scan_installed_pkg(&name, fam, &arg.pkg)?;
     if !cli.bin.is_empty() { bins = cli.bin.clone(); }
     if !cli.app.is_empty() { apps = cli.app.clone(); }
-    if cli.dry_run {
-        println!("--dry-run: would export bins={:?}, apps={:?}", bins, apps);
-        return Ok(());
-    }
     export_items(&name, &bins, &apps)
 }
 
-fn uninstall_pkg(cli: &Cli, arg: PkgArg) -> Result<()> {
+fn uninstall_pkg(cli: &Cli, arg: UninstallArgs) -> Result<()> {
     let name = cli.container.clone().ok_or_else(|| anyhow!("--container is required for uninstall"))?;
     let fam = distro::classify_box_family(&name)?;
-    let (bins, apps) = scan_installed_pkg(&name, fam, &arg.pkg).unwrap_or_default();
-    if !bins.is_empty() || !apps.is_empty() {
-        println!("Removing exports for package '{}'...", arg.pkg);
-        if !cli.dry_run { unexport_items(&name, &bins, &apps); }
+    let doomed = removal_preview(&name, fam, &arg.pkg, arg.autoremove)?;
+    println!("The following {} package(s) will be removed from '{}':", doomed.len(), name);
+    for p in &doomed { println!("  {}", p); }
+    let interactive = std::io::stdin().is_terminal() && std::io::stdout().is_terminal();
+    if interactive && !cli.dry_run {
+        print!("Proceed? [y/N] ");
+        use std::io::Write; let _ = std::io::stdout().flush();
+        let mut buf = String::new(); let _ = std::io::stdin().read_line(&mut buf);
+        let ans = buf.trim().to_ascii_lowercase();
+        if ans != "y" && ans != "yes" {
+            println!("Uninstall cancelled.");
+            return Ok(());
+        }
+    }
+    // Dependents and orphans go too, so their exports have to be removed as well. Their file
+    // lists are read now, while the packages are still installed
+    let mut exports = Vec::new();
+    for p in &doomed {
+        let (bins, apps) = scan_installed_pkg(&name, fam, p).unwrap_or_default();
+        if !bins.is_empty() || !apps.is_empty() { exports.push((p, bins, apps)); }
+    }
+    let ok = uninstall_inside(&name, fam, &arg.pkg, arg.autoremove)?;
+    if ok {
+        if !dryrun::enabled() { println!("Uninstall completed."); }
+        for (p, bins, apps) in &exports {
+            println!("Removing exports for package '{}'...", p);
+            unexport_items(&name, bins, apps);
+        }
+        // A rebuild should not bring back what was uninstalled
+        for p in &doomed { archive::forget(&name, p)?; }
+    } else {
+        println!("Uninstall command reported failure.");
     }
-    let ok = uninstall_inside(&name, fam, &arg.pkg, cli.dry_run)?;
-    if ok { println!("Uninstall completed."); } else { println!("Uninstall command reported failure."); }
     Ok(())
 }
 
+/// Simulate the removal and list every package it would take out, reverse dependencies included.
+fn removal_preview(box_name: &str, fam: BoxFamily, pkg: &str, autoremove: bool) -> Result<Vec<String>> {
+    // Simulations exit non-zero on purpose (dnf --assumeno), so judge them by what they list
+    let out = distro::enter_capture_elevated(box_name, &pkgops::remove_preview_cmd(fam, pkg, autoremove))?;
+    let doomed = pkgops::parse_remove_preview(fam, &String::from_utf8_lossy(&out.stdout));
+    if doomed.is_empty() {
+        let err = String::from_utf8_lossy(&out.stderr);
+        let detail = err.lines().rfind(|l| !l.trim().is_empty()).unwrap_or("nothing would be removed");
+        return Err(anyhow!("cannot remove '{}' from '{}': {}", pkg, box_name, detail.trim()));
+    }
+    Ok(doomed)
+}
+
 fn unexport_items(box_name: &str, bins: &[String], apps: &[String]) {
     let supports = dbe_supports_container_flag();
     for b in bins {
+        let target = host_bin_dir().join(b);
+        let existed = target.exists();
         if supports {
-            let _ = std::process::Command::new("distrobox-export").args(["--container", box_name, "--delete", "--bin", b]).status();
+            let _ = dryrun::status(std::process::Command::new("distrobox-export").args(["--container", box_name, "--delete", "--bin", b]));
         } else {
             // Older versions expect absolute path and to be run inside the container
             let abs = format!("/usr/bin/{}", b);
-            let _ = std::process::Command::new("distrobox").args(["enter", "-n", box_name, "--", "distrobox-export", "--delete", "--bin", &abs]).status();
+            let _ = dryrun::status(std::process::Command::new("distrobox").args(["enter", "-n", box_name, "--", "distrobox-export", "--delete", "--bin", &abs]));
         }
+        journal::record_outcome(&target, existed);
     }
     for app in apps {
         let base = std::path::Path::new(app).file_name().and_then(|s| s.to_str()).unwrap_or(app);
+        let target = exported_app_path(box_name, base);
+        let existed = target.exists();
         if supports {
-            let _ = std::process::Command::new("distrobox-export").args(["--container", box_name, "--delete", "--app", base]).status();
+            let _ = dryrun::status(std::process::Command::new("distrobox-export").args(["--container", box_name, "--delete", "--app", base]));
         } else {
-            let _ = std::process::Command::new("distrobox").args(["enter", "-n", box_name, "--", "distrobox-export", "--delete", "--app", base]).status();
+            let _ = dryrun::status(std::process::Command::new("distrobox").args(["enter", "-n", box_name, "--", "distrobox-export", "--delete", "--app", base]));
         }
+        journal::record_outcome(&target, existed);
     }
 }
 
-fn uninstall_inside(box_name: &str, fam: BoxFamily, pkg: &str, dry_run: bool) -> Result<bool> {
-    let p = shell_escape::escape(std::borrow::Cow::from(pkg.to_string()));
-    let inner = match fam {
-        BoxFamily::Debian => format!("set -e; if command -v apt-get >/dev/null; then apt-get -y remove {}; else dpkg -r {}; fi", p, p),
-        BoxFamily::Fedora => format!("set -e; if command -v dnf >/dev/null; then dnf -y remove {}; else rpm -e {}; fi", p, p),
-        BoxFamily::OpenSuse => format!("set -e; if command -v zypper >/dev/null; then zypper --non-interactive rm {}; else rpm -e {}; fi", p, p),
-        BoxFamily::Arch => format!("set -e; if command -v pacman >/dev/null; then pacman -R --noconfirm {}; else echo 'pacman not found' >&2; exit 1; fi", p),
-    };
-    let cmd = format!(
-        "set -e; if command -v sudo >/dev/null; then if sudo -n true >/dev/null 2>&1; then sudo sh -lc '{}' ; else sudo sh -lc '{}' ; fi; elif command -v doas >/dev/null; then doas sh -lc '{}' ; else sh -lc '{}' ; fi",
-        inner, inner, inner, inner
-    );
-    if dry_run { println!("--dry-run: would run inside '{}': {}", box_name, cmd); return Ok(true); }
-    distro::enter_status(box_name, &cmd, false)
+fn uninstall_inside(box_name: &str, fam: BoxFamily, pkg: &str, autoremove: bool) -> Result<bool> {
+    distro::enter_status_elevated_inherit(box_name, &pkgops::remove_cmd(fam, pkg, autoremove))
 }
 
-fn preseed_password(box_name: &str, password: &str) -> Result<()> {
-    // One-time, opt-in: set the user password via root so future sudo prompts work non-interactively too
-    // Determine a likely non-root user from /etc/passwd (first uid >= 1000), then set its password via chpasswd
-    return preseed_password_root(box_name, password);
-    /*
-    let pw = shell_escape::escape(std::borrow::Cow::from(password.to_string()));
-    let cmd = format!(
-        "u=$(awk -F: '$3>=1000 && $1!="nobody" {print $1; exit}' /etc/passwd); \
-         if [ -z \"$u\" ]; then u=$(getent passwd 1000 | cut -d: -f1 || true); fi; \
-         if [ -z \"$u\" ]; then u=$(getent passwd 1001 | cut -d: -f1 || true); fi; \
-         if [ -z \"$u\" ]; then echo 'no non-root user found' >&2; exit 1; fi; \
-         printf '%s:%s\n' \"$u\" {} | chpasswd",
-        pw
-    );
-    let ok = distro::enter_status(box_name, &cmd, true).context("setting initial password via chpasswd (root)")?;
-    if !ok { return Err(anyhow!("failed to set initial password in container")); }
-    */
-    Ok(())
+/// Password from `--password-fd`, `--password-stdin` or `--password-file`, without its line ending.
+fn read_password(cli: &Cli) -> Result<Option<String>> {
+    let raw = if let Some(fd) = cli.password_fd {
+        std::fs::read_to_string(format!("/dev/fd/{}", fd)).with_context(|| format!("reading password from fd {}", fd))?
+    } else if cli.password_stdin {
+        let mut line = String::new();
+        std::io::stdin().read_line(&mut line).context("reading password from stdin")?;
+        line
+    } else if let Some(path) = &cli.password_file {
+        #[cfg(unix)] {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(path).with_context(|| format!("reading {}", path.display()))?.permissions().mode();
+            if mode & 0o077 != 0 {
+                return Err(anyhow!("{} is accessible by other users (mode {:o}); chmod 600 it", path.display(), mode & 0o777));
+            }
+        }
+        std::fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?
+    } else {
+        return Ok(None);
+    };
+    let pw = raw.lines().next().unwrap_or("").to_string();
+    if pw.is_empty() { return Err(anyhow!("empty password")); }
+    Ok(Some(pw))
 }
 
-fn preseed_password_root(box_name: &str, password: &str) -> Result<()> {
+fn preseed_password(box_name: &str, password: &str) -> Result<()> {
     // Opt-in: set the password for the first non-system user (uid>=1000) via chpasswd as root.
-    let pw = shell_escape::escape(std::borrow::Cow::from(password.to_string()));
-    // Verify we can enter as root; if not, skip silently
-    if !distro::enter_status(box_name, "true", true).unwrap_or(false) {
-        return Ok(());
-    }
-    let cmd = format!(r#"u=$(awk -F: '$3>=1000 && $1!="nobody" {{print $1; exit}}' /etc/passwd); \
+    // The password travels on stdin so it never shows up in process listings.
+    let cmd = r#"u=$(awk -F: '$3>=1000 && $1!="nobody" {print $1; exit}' /etc/passwd); \
          if [ -z "$u" ]; then u=$(getent passwd 1000 | cut -d: -f1 || true); fi; \
          if [ -z "$u" ]; then u=$(getent passwd 1001 | cut -d: -f1 || true); fi; \
          if [ -z "$u" ]; then echo 'no non-root user found' >&2; exit 1; fi; \
-         printf '%s:%s\n' "$u" {} | chpasswd"#, pw);
-    let ok = distro::enter_status(box_name, &cmd, true).context("setting initial password via chpasswd (root)")?;
-    if !ok { return Err(anyhow!("failed to set initial password in container")); }
+         IFS= read -r pw; printf '%s:%s\n' "$u" "$pw" | chpasswd"#;
+    let ok = distro::enter_status_elevated_with_input(box_name, cmd, format!("{}\n", password).as_bytes())
+        .with_context(|| format!("setting the initial password in '{}'", box_name))?;
+    if !ok {
+        return Err(anyhow!("could not set the initial password in '{}': chpasswd as root failed (is sudo or doas usable in the box?)", box_name));
+    }
     Ok(())
 }
 
-fn pm_cmd(cmd: PmCmd) -> Result<()> {
+fn pm_cmd(cmd: PmCmd, cli: &Cli) -> Result<()> {
     match cmd {
         PmCmd::SetDefault { family, box_name } => pm::set_default(to_family(family), &box_name),
         PmCmd::GenerateShims => pm::generate_shims(),
         PmCmd::ShowDefaults => {
-            let map = pm::show_defaults();
+            let map = pm::show_defaults()?;
             if map.is_empty() { println!("No defaults set."); } else { for (k, v) in map { println!("{} => {}", k, v); } }
             Ok(())
         }
-        PmCmd::Snapshot => pm_snapshot(),
-        PmCmd::PostTransaction => pm_post_transaction(),
+        PmCmd::Snapshot => {
+            let container = pm_container(cli, "pm snapshot")?;
+            snapshot_box(&container, cli.family.map(to_family))
+        }
+        PmCmd::PostTransaction => {
+            let container = pm_container(cli, "pm post-transaction")?;
+            let fam = distro::classify_box_family(&container)?;
+            post_transaction_box(&container, fam).map(|_| ())
+        }
+        PmCmd::ListShims => pm::list_shims(),
+        PmCmd::Regenerate { force } => pm::regenerate_shims(force),
+        PmCmd::Verify => pm::verify_shims(),
+        PmCmd::RemoveShims { force } => pm::remove_shims(force),
+        PmCmd::Exec { manager, args } => pm_exec(&manager, &args, cli),
     }
 }
 
-fn pm_snapshot() -> Result<()> {
-    let name = std::env::args().collect::<Vec<_>>(); // container is passed via global --container
-    // Resolve container and family
-    let container = std::env::args().skip_while(|a| a != "--container").nth(1)
+fn pm_container(cli: &Cli, what: &str) -> Result<String> {
+    cli.container.clone()
         .or_else(|| std::env::var("PKGBRIDGE_CONTAINER").ok())
-        .ok_or_else(|| anyhow!("--container is required for pm snapshot"))?;
-    let fam = std::env::args().skip_while(|a| a != "--family").nth(1)
-        .or_else(|| Some(format_family(distro::classify_box_family(&container).ok()?)).map(|s| s.to_string()))
-        .unwrap_or_else(|| "".into());
-    let list = list_installed_pkgs(&container, None)?;
-    std::fs::create_dir_all(crate::config::snapshot_dir()).ok();
-    std::fs::write(crate::config::snapshot_path(&container), list.join("\n"))?;
+        .ok_or_else(|| anyhow!("--container is required for {}", what))
+}
+
+/// Run a package manager in a box resolved at run time: snapshot, elevate, run, then export what changed.
+/// Exits with the manager's own status so shims behave like the real command.
+fn pm_exec(manager: &str, args: &[String], cli: &Cli) -> Result<()> {
+    let def = pm::manager(manager)
+        .or_else(|| pm::family_from_key(manager).map(pm::family_manager))
+        .ok_or_else(|| anyhow!("unknown package manager or family '{}'", manager))?;
+    let fam = def.family;
+    let box_name = match cli.container.clone().or(pm::default_box(fam)?) {
+        Some(b) => b,
+        None => bootstrap_default_box(fam, def.name, cli)?,
+    };
+    let mut argv = vec![def.name.to_string()];
+    argv.extend(args.iter().cloned());
+    let kind = def.classify(args);
+    // Read-only queries skip the snapshot and elevation so they never prompt
+    if kind == pm::CallKind::ReadOnly {
+        let code = distro::enter_argv(&box_name, &argv)?;
+        if code != 0 { std::process::exit(code); }
+        return Ok(());
+    }
+    if !dryrun::perform(format_args!("run '{}' in '{}'", argv.join(" "), box_name)) { return Ok(()); }
+    let transaction = kind == pm::CallKind::Transaction;
+    if transaction {
+        if let Err(e) = snapshot_box(&box_name, Some(fam)) {
+            log::debug!("snapshot of '{}' failed: {:#}", box_name, e);
+        }
+    }
+    // Never container root: the user's sudo/doas forwards password prompts to this terminal
+    if def.elevate {
+        if let Some(helper) = distro::elevation_helper(&box_name) { argv.insert(0, helper.to_string()); }
+    }
+    let mut code = distro::enter_argv(&box_name, &argv)?;
+    if code == 127 {
+        if let Some(helper) = config::AurHelper::from_name(def.name) {
+            // The shim exists on the host before the helper exists in the box
+            if offer_aur_helper(&box_name, helper)? { code = distro::enter_argv(&box_name, &argv)?; }
+        }
+    }
+    // Even a failed transaction may have changed packages, so always look for new apps
+    if transaction {
+        if let Err(e) = post_transaction_box(&box_name, fam) {
+            log::warn!("post-transaction export for '{}' failed: {:#}", box_name, e);
+        }
+    }
+    if code != 0 { std::process::exit(code); }
     Ok(())
 }
 
-fn pm_post_transaction() -> Result<()> {
-    let container = std::env::args().skip_while(|a| a != "--container").nth(1)
-        .or_else(|| std::env::var("PKGBRIDGE_CONTAINER").ok())
-        .ok_or_else(|| anyhow!("--container is required for pm post-transaction"))?;
-    let fam = distro::classify_box_family(&container)?;
-    let before = std::fs::read_to_string(crate::config::snapshot_path(&container)).unwrap_or_default();
+/// The AUR helper is missing from `box_name`: offer to build it there. Returns whether it now exists.
+fn offer_aur_helper(box_name: &str, helper: config::AurHelper) -> Result<bool> {
+    if distro::enter_capture(box_name, &format!("command -v {}", helper.name()), false)?.status.success() { return Ok(false); }
+    if !(std::io::stdin().is_terminal() && std::io::stderr().is_terminal()) {
+        eprintln!("pkgbridge: '{}' is not installed in '{}'; set aur_helper = \"{}\" in config.toml and run: pkgbridge box provision {}", helper.name(), box_name, helper.name(), box_name);
+        return Ok(false);
+    }
+    eprint!("'{}' is not installed in '{}'. Build it from the AUR now? [Y/n] ", helper.name(), box_name);
+    use std::io::Write; let _ = std::io::stderr().flush();
+    let mut buf = String::new(); let _ = std::io::stdin().read_line(&mut buf);
+    let ans = buf.trim().to_ascii_lowercase();
+    if !(ans.is_empty() || ans == "y" || ans == "yes") { return Ok(false); }
+    provision::install_aur_helper(box_name, helper)?;
+    Ok(true)
+}
+
+/// No default box for `fam` yet: offer to create the family's default box and make it the default.
+fn bootstrap_default_box(fam: BoxFamily, mgr: &str, cli: &Cli) -> Result<String> {
+    let key = pm::family_key(fam);
+    let (default_name, default_image) = default_box_for_family(fam);
+    let chosen_image = cli.create_image.clone()
+        .or(provision::template_image(fam)?)
+        .unwrap_or_else(|| default_image.to_string());
+    let mut create = cli.create;
+    if !create && std::io::stdin().is_terminal() && std::io::stderr().is_terminal() {
+        eprintln!("pkgbridge: '{}' not found on host and no default {} box is set.", mgr, key);
+        eprint!("Create a {} box '{}' from '{}' and run '{}' from it? [Y/n] ", key, default_name, chosen_image, mgr);
+        use std::io::Write; let _ = std::io::stderr().flush();
+        let mut buf = String::new(); let _ = std::io::stdin().read_line(&mut buf);
+        let ans = buf.trim().to_ascii_lowercase();
+        create = ans.is_empty() || ans == "y" || ans == "yes";
+    }
+    if !create {
+        return Err(anyhow!("no default {} box; run: pkgbridge pm set-default {} <box> (or pass --create)", key, key));
+    }
+    if !distro::discover_boxes().unwrap_or_default().iter().any(|b| b.name == default_name) {
+        eprintln!("Creating '{}' from '{}'...", default_name, chosen_image);
+        provision::create_and_provision(default_name, &chosen_image, fam)?;
+    }
+    pm::set_default(fam, default_name)?;
+    Ok(default_name.to_string())
+}
+
+fn snapshot_box(container: &str, fam: Option<BoxFamily>) -> Result<()> {
+    let list = list_installed_pkgs(container, fam)?;
+    dryrun::create_dir_all(&crate::config::snapshot_dir()).ok();
+    dryrun::write(&crate::config::snapshot_path(container), list.join("\n"))?;
+    Ok(())
+}
+
+/// Packages that changed since the last snapshot of a box.
+#[derive(Debug, Default)]
+struct TxChanges {
+    new: Vec<String>,
+    upgraded: Vec<(String, String, String)>, // name, before, after
+}
+
+/// Diff installed packages against the snapshot, export new/upgraded ones, and roll the snapshot forward.
+fn post_transaction_box(container: &str, fam: BoxFamily) -> Result<TxChanges> {
+    // Under --dry-run the transaction was only printed and the snapshot not refreshed
+    if dryrun::enabled() { return Ok(TxChanges::default()); }
+    let before = std::fs::read_to_string(crate::config::snapshot_path(container)).unwrap_or_default();
     let before_set: std::collections::HashMap<String, String> = before.lines().filter_map(|l| {
         let mut sp = l.splitn(2, '\t');
         Some((sp.next()?.to_string(), sp.next().unwrap_or("").to_string()))
     }).collect();
-    let after_list = list_installed_pkgs(&container, Some(fam))?;
+    let after_list = list_installed_pkgs(container, Some(fam))?;
     let mut after_map: std::collections::HashMap<String, String> = std::collections::HashMap::new();
     for l in &after_list { let mut sp = l.splitn(2, '\t'); if let (Some(n), Some(v)) = (sp.next(), sp.next()) { after_map.insert(n.to_string(), v.to_string()); } }
-    let mut new_pkgs = Vec::new();
-    let mut upgraded = Vec::new();
+    let mut changes = TxChanges::default();
     for (name, ver) in &after_map {
         match before_set.get(name) {
-            None => new_pkgs.push(name.clone()),
-            Some(prev) if prev != ver => upgraded.push(name.clone()),
+            None => changes.new.push(name.clone()),
+            Some(prev) if prev != ver => changes.upgraded.push((name.clone(), prev.clone(), ver.clone())),
             _ => {}
         }
     }
-    if new_pkgs.is_empty() && upgraded.is_empty() { return Ok(()); }
-    log::info!("Detected new: {:?}, upgraded: {:?}", new_pkgs, upgraded);
-    let mut pkgs: Vec<String> = new_pkgs;
-    pkgs.extend(upgraded);
+    changes.new.sort();
+    changes.upgraded.sort();
+    if changes.new.is_empty() && changes.upgraded.is_empty() { return Ok(changes); }
+    log::info!("Detected new: {:?}, upgraded: {:?}", changes.new, changes.upgraded);
+    let pkgs = changes.new.iter().chain(changes.upgraded.iter().map(|(n, _, _)| n));
     for pkg in pkgs {
-        let (bins, apps) = scan_installed_pkg(&container, fam, &pkg).unwrap_or_default();
-        let _ = export_items(&container, &to_names_only(bins), &apps);
+        let (bins, apps) = scan_installed_pkg(container, fam, pkg).unwrap_or_default();
+        let _ = export_items(container, &bins, &apps);
     }
     // Update snapshot to after state
-    std::fs::write(crate::config::snapshot_path(&container), after_list.join("\n"))?;
-    Ok(())
+    dryrun::write(&crate::config::snapshot_path(container), after_list.join("\n"))?;
+    Ok(changes)
 }
 
 fn list_installed_pkgs(container: &str, fam: Option<BoxFamily>) -> Result<Vec<String>> {
-    let fam = fam.unwrap_or(distro::classify_box_family(container)?);
-    let cmd = match fam {
-        BoxFamily::Debian => "dpkg-query -W -f='${Package}\t${Version}\n'".to_string(),
-        BoxFamily::Fedora | BoxFamily::OpenSuse | BoxFamily::Arch => "rpm -qa --qf '%{NAME}\t%{VERSION}-%{RELEASE}\n'".to_string(),
-    };
-    let out = distro::enter_capture(container, &cmd, false)?;
+    let fam = match fam { Some(f) => f, None => distro::classify_box_family(container)? };
+    let out = distro::enter_capture(container, &pkgops::list_installed_cmd(fam), false)?;
     let s = String::from_utf8_lossy(&out.stdout);
-    Ok(s.lines().map(|l| l.trim().to_string()).filter(|l| !l.is_empty()).collect())
+    // Normalize to name<TAB>version regardless of the manager's separator
+    Ok(s.lines()
+        .map(|l| l.trim())
+        .filter(|l| !l.is_empty())
+        .map(|l| if l.contains('\t') { l.to_string() } else { l.replacen(' ', "\t", 1) })
+        .collect())
+}
+
+fn desktop_cmd(cmd: DesktopCmd) -> Result<()> {
+    match cmd {
+        DesktopCmd::Install => desktop::install(),
+        DesktopCmd::Uninstall => desktop::uninstall(),
+    }
+}
+
+fn box_cmd(cmd: BoxCmd, cli: &Cli) -> Result<()> {
+    match cmd {
+        BoxCmd::Create { name } => box_create(name, cli),
+        BoxCmd::Start { name } => {
+            distro::start_box(&name)?;
+            if !dryrun::enabled() { println!("Started '{}'.", name); }
+            Ok(())
+        }
+        BoxCmd::Stop { name } => {
+            distro::stop_box(&name)?;
+            if !dryrun::enabled() { println!("Stopped '{}'.", name); }
+            Ok(())
+        }
+        BoxCmd::Rm { name } => box_rm(&name),
+        BoxCmd::Rebuild { name } => box_rebuild(&name, cli),
+        BoxCmd::Nopasswd { name, revoke } => {
+            if revoke {
+                provision::revoke_pm_without_password(&name)?;
+                if !dryrun::enabled() { println!("Removed the package-manager sudoers rule from '{}'.", name); }
+            } else {
+                let fam = distro::classify_box_family(&name)?;
+                provision::allow_pm_without_password(&name, fam)?;
+                if !dryrun::enabled() { println!("Package managers in '{}' now run through sudo without a password.", name); }
+            }
+            Ok(())
+        }
+        BoxCmd::Upgrade { name } => box_upgrade(&name).map(|_| ()),
+        BoxCmd::Enter { name } => {
+            distro::enter_interactive(&name)?;
+            Ok(())
+        }
+        BoxCmd::Provision { name } => {
+            let fam = distro::classify_box_family(&name)?;
+            provision::provision_box(&name, fam)
+        }
+    }
+}
+
+fn box_create(name: Option<String>, cli: &Cli) -> Result<()> {
+    let fam = cli.family.map(to_family);
+    let image = cli.create_image.clone()
+        .or(fam.map(provision::template_image).transpose()?.flatten())
+        .or_else(|| fam.map(|f| default_box_for_family(f).1.to_string()))
+        .ok_or_else(|| anyhow!("pass --family or --create-image to choose an image"))?;
+    let name = name
+        .or_else(|| fam.map(|f| default_box_for_family(f).0.to_string()))
+        .ok_or_else(|| anyhow!("pass a box name"))?;
+    if distro::discover_boxes().unwrap_or_default().iter().any(|b| b.name == name) {
+        return Err(anyhow!("box '{}' already exists", name));
+    }
+    println!("Creating '{}' from '{}'...", name, image);
+    distro::create_box(&name, &image)?;
+    // Under --dry-run there is no box to look into yet, so the image name has to do
+    let fam = match fam {
+        Some(f) => f,
+        None => distro::classify_box_family(&name).or_else(|e| distro::guess_family_from_image(&image).ok_or(e))?,
+    };
+    provision::provision_box(&name, fam)?;
+    if !dryrun::enabled() { println!("Created '{}' ({}).", name, format_family(fam)); }
+    Ok(())
+}
+
+fn box_rm(name: &str) -> Result<()> {
+    let mut entries = hostfiles::bound_to_box(&host_bin_dir(), name);
+    entries.extend(hostfiles::bound_to_box(&host_apps_dir(), name));
+    distro::remove_box(name)?;
+    for e in &entries {
+        match dryrun::remove_file(&e.path) {
+            Ok(()) if !dryrun::enabled() => println!("Removed {} {}", e.kind.label(), e.path.display()),
+            Ok(()) => {}
+            Err(err) => eprintln!("Warning: could not remove {}: {}", e.path.display(), err),
+        }
+    }
+    if entries.iter().any(|e| e.kind == hostfiles::Kind::DesktopEntry) {
+        let _ = dryrun::status(std::process::Command::new("update-desktop-database").arg(host_apps_dir()));
+    }
+    // Forget defaults, snapshots and provisioning records bound to the box
+    let mut cfg = config::load_config()?;
+    let before = cfg.pm_defaults.len();
+    cfg.pm_defaults.retain(|_, b| b != name);
+    if cfg.pm_defaults.len() != before { config::save_config(&cfg)?; }
+    let mut st = config::load_state()?;
+    let provisioned = st.provisioned.remove(name).is_some();
+    let staged = st.staged.remove(name).is_some();
+    let repos = st.repos.remove(name).is_some();
+    let holds = st.holds.remove(name).is_some();
+    let shims_before = st.shims.len();
+    st.shims.retain(|p, _| !entries.iter().any(|e| e.path == std::path::Path::new(p)));
+    if provisioned || staged || repos || holds || st.shims.len() != shims_before { config::save_state(&st)?; }
+    let _ = dryrun::remove_file(&config::snapshot_path(name));
+    if !dryrun::enabled() { println!("Removed box '{}'.", name); }
+    Ok(())
+}
+
+fn box_rebuild(name: &str, cli: &Cli) -> Result<()> {
+    let image = distro::discover_boxes()
+        .context("discovering boxes")?
+        .into_iter()
+        .find(|b| b.name == name)
+        .ok_or_else(|| anyhow!("box '{}' not found", name))?
+        .image
+        .ok_or_else(|| anyhow!("could not determine the image of '{}'", name))?;
+    let fam = distro::classify_box_family(name)?;
+    let packages = archive::latest_per_package(name);
+    let interactive = std::io::stdin().is_terminal() && std::io::stdout().is_terminal();
+    if interactive && !cli.dry_run {
+        print!("Everything in '{}' outside its home will be replaced by a fresh box from '{}'. Proceed? [y/N] ", name, image);
+        use std::io::Write; let _ = std::io::stdout().flush();
+        let mut buf = String::new(); let _ = std::io::stdin().read_line(&mut buf);
+        let ans = buf.trim().to_ascii_lowercase();
+        if ans != "y" && ans != "yes" {
+            println!("Rebuild cancelled.");
+            return Ok(());
+        }
+    }
+    let options = distro::create_options(name)?;
+    let manager = distro::container_manager(name)?;
+    // The new box is built and provisioned under a temporary name, so a failed pull, create or
+    // provisioning step leaves the old box untouched
+    let temp = distro::temp_box_name(name);
+    println!("Creating a new '{}' from '{}'...", name, image);
+    eprintln!("Warning: options given to `distrobox create` with --additional-flags or --unshare-* are not carried over to the new box");
+    let mut st = config::load_state()?;
+    if st.provisioned.remove(&temp).is_some() { config::save_state(&st)?; }
+    distro::create_box_with(&temp, &image, &options).with_context(|| format!("'{}' was left unchanged", name))?;
+    if let Err(e) = provision::provision_box(&temp, fam) {
+        if let Err(rm) = distro::remove_box(&temp) { eprintln!("Warning: could not remove '{}': {:#}", temp, rm); }
+        return Err(e.context(format!("'{}' was left unchanged", name)));
+    }
+    distro::replace_box(name, &temp, &manager)?;
+    // The new container starts clean: its provisioning records move over and staging records no longer apply.
+    // Holds stay recorded; their native locks are put back once the packages are reinstalled
+    let mut st = config::load_state()?;
+    match st.provisioned.remove(&temp) {
+        Some(done) => { st.provisioned.insert(name.to_string(), done); }
+        None => { st.provisioned.remove(name); }
+    }
+    st.staged.remove(name);
+    let holds = st.holds.get(name).cloned().unwrap_or_default();
+    config::save_state(&st)?;
+    let _ = dryrun::remove_file(&config::snapshot_path(name));
+    for rec in repos::list(name)? {
+        println!("Adding repository '{}'...", rec.name);
+        if let Err(e) = repos::apply(name, fam, &rec) { eprintln!("Warning: adding repository '{}' failed: {:#}", rec.name, e); }
+    }
+    let mut failed: Vec<String> = Vec::new();
+    for a in &packages {
+        println!("Reinstalling {} {}...", a.name, a.version);
+        if let Err(e) = install_like(&a.path, cli, Some(name), false) {
+            eprintln!("Warning: reinstalling {} failed: {:#}", a.name, e);
+            failed.push(a.name.clone());
+        }
+    }
+    // Holds go on last so they pin the reinstalled versions
+    for pkg in &holds {
+        match distro::enter_status_elevated(name, &pkgops::hold_cmd(fam, pkg)) {
+            Ok(true) if !dryrun::enabled() => println!("Held '{}'.", pkg),
+            Ok(true) => {}
+            Ok(false) => eprintln!("Warning: holding '{}' failed; run `pkgbridge hold {} {}` again", pkg, name, pkg),
+            Err(e) => eprintln!("Warning: holding '{}' failed: {:#}", pkg, e),
+        }
+    }
+    if !dryrun::enabled() { println!("Rebuilt '{}' ({}); reinstalled {} of {} archived package(s).", name, format_family(fam), packages.len() - failed.len(), packages.len()); }
+    if !failed.is_empty() { return Err(anyhow!("failed to reinstall: {}", failed.join(", "))); }
+    Ok(())
+}
+
+fn reinstall(box_name: &str, pkg: &str, version: Option<&str>, cli: &Cli) -> Result<()> {
+    let Some(a) = archive::find(box_name, pkg, version) else {
+        let known: Vec<String> = archive::list(box_name).into_iter().filter(|a| a.name == pkg).map(|a| a.version).collect();
+        return Err(match (version, known.is_empty()) {
+            (Some(v), false) => anyhow!("{} {} is not archived for '{}' (archived: {})", pkg, v, box_name, known.join(", ")),
+            _ => anyhow!("no archived package '{}' for '{}'; only packages installed from a file are archived", pkg, box_name),
+        });
+    };
+    println!("Reinstalling {} {} from {}", a.name, a.version, a.path.display());
+    install_like(&a.path, cli, Some(box_name), true)
+}
+
+fn box_upgrade(name: &str) -> Result<TxChanges> {
+    let fam = distro::classify_box_family(name)?;
+    let cmd = pkgops::upgrade_cmd(fam);
+    snapshot_box(name, Some(fam))?;
+    println!("Upgrading '{}' ({})...", name, format_family(fam));
+    let held = held_pkgs(name)?;
+    if !held.is_empty() { println!("Keeping held package(s) at their installed version: {}", held.join(", ")); }
+    if !distro::enter_status_elevated_inherit(name, &cmd)? {
+        return Err(anyhow!("upgrade failed inside '{}'", name));
+    }
+    let changes = post_transaction_box(name, fam)?;
+    for (pkg, from, to) in changes.upgraded.iter().filter(|(p, _, _)| held.contains(p)) {
+        eprintln!("Warning: held package '{}' moved from {} to {}; check the lock with `pkgbridge hold {} {}`", pkg, from, to, name, pkg);
+    }
+    if !dryrun::enabled() { println!("Upgraded {} package(s) and installed {} new in '{}'.", changes.upgraded.len(), changes.new.len(), name); }
+    Ok(changes)
+}
+
+fn upgrade_boxes(args: &UpgradeArgs, cli: &Cli) -> Result<()> {
+    let names: Vec<String> = match (&cli.container, args.all) {
+        (Some(name), false) => vec![name.clone()],
+        (None, true) => distro::discover_boxes().context("discovering boxes")?.into_iter().map(|b| b.name).collect(),
+        (Some(_), true) => return Err(anyhow!("pass either --all or --container, not both")),
+        (None, false) => return Err(anyhow!("pass --all to upgrade every box, or --container <box>")),
+    };
+    if names.is_empty() {
+        println!("No boxes found (is 'distrobox' installed?)");
+        return Ok(());
+    }
+    let mut results: Vec<(String, Result<TxChanges>)> = Vec::new();
+    for name in names {
+        let res = box_upgrade(&name);
+        if let Err(e) = &res { eprintln!("Warning: upgrading '{}' failed: {:#}", name, e); }
+        results.push((name, res));
+    }
+    if dryrun::enabled() { return Ok(()); }
+    println!();
+    println!("Upgrade summary:");
+    let mut failed = 0usize;
+    for (name, res) in &results {
+        match res {
+            Ok(ch) if ch.upgraded.is_empty() && ch.new.is_empty() => println!("{}: up to date", name),
+            Ok(ch) => {
+                println!("{}: {} upgraded, {} new", name, ch.upgraded.len(), ch.new.len());
+                for (pkg, from, to) in &ch.upgraded { println!("  {} {} -> {}", pkg, from, to); }
+                for pkg in &ch.new { println!("  {} (new)", pkg); }
+            }
+            Err(e) => {
+                failed += 1;
+                println!("{}: FAILED ({})", name, e);
+            }
+        }
+    }
+    if failed > 0 { return Err(anyhow!("{} of {} box(es) failed to upgrade", failed, results.len())); }
+    Ok(())
+}
+
+fn clean(cli: &Cli) -> Result<()> {
+    let names: Vec<String> = match &cli.container {
+        Some(name) => vec![name.clone()],
+        None => distro::discover_boxes().context("discovering boxes")?.into_iter().map(|b| b.name).collect(),
+    };
+    if names.is_empty() {
+        println!("No boxes found (is 'distrobox' installed?)");
+        return Ok(());
+    }
+    let mut failed = 0usize;
+    for name in &names {
+        let cache = match distro::classify_box_family(name) {
+            Ok(fam) => pkgops::clean_cache_cmd(fam),
+            Err(e) => {
+                log::warn!("could not classify '{}': {:#}; only removing staged files", name, e);
+                "true".to_string()
+            }
+        };
+        let cmd = format!("rm -rf {}; {}", distro::STAGING_DIR, cache);
+        match distro::enter_status_elevated(name, &cmd) {
+            Ok(true) if dryrun::enabled() => {}
+            Ok(true) => {
+                println!("{}: cleaned", name);
+                let mut st = config::load_state()?;
+                if st.staged.remove(name).is_some() { config::save_state(&st)?; }
+            }
+            Ok(false) => { failed += 1; println!("{}: FAILED", name); }
+            Err(e) => { failed += 1; println!("{}: FAILED ({:#})", name, e); }
+        }
+    }
+    if failed > 0 { return Err(anyhow!("{} of {} box(es) could not be cleaned", failed, names.len())); }
+    Ok(())
+}
+
+fn search(args: &SearchArgs, cli: &Cli) -> Result<()> {
+    // One box per family: the configured defaults, or just the requested container
+    let targets: Vec<(String, BoxFamily)> = if let Some(name) = &cli.container {
+        vec![(name.clone(), distro::classify_box_family(name)?)]
+    } else {
+        let mut t: Vec<(String, BoxFamily)> = pm::show_defaults()?
+            .into_iter()
+            .filter_map(|(k, b)| pm::family_from_key(&k).map(|f| (b, f)))
+            .collect();
+        t.sort_by(|a, b| a.0.cmp(&b.0));
+        t.dedup_by(|a, b| a.0 == b.0);
+        t
+    };
+    if targets.is_empty() {
+        return Err(anyhow!("no default boxes configured; run 'pkgbridge pm set-default <family> <box>' or pass --container"));
+    }
+    let results: Vec<(String, BoxFamily, Result<Vec<pkgops::SearchHit>>)> = std::thread::scope(|s| {
+        let handles: Vec<_> = targets
+            .iter()
+            .map(|(name, fam)| {
+                let term = args.term.as_str();
+                s.spawn(move || {
+                    let out = distro::enter_capture(name, &pkgops::search_cmd(*fam, term), false);
+                    out.map(|o| pkgops::parse_search(*fam, &String::from_utf8_lossy(&o.stdout)))
+                })
+            })
+            .collect();
+        targets
+            .iter()
+            .zip(handles)
+            .map(|((name, fam), h)| {
+                let res = h.join().unwrap_or_else(|_| Err(anyhow!("search thread panicked")));
+                (name.clone(), *fam, res)
+            })
+            .collect()
+    });
+    let mut total = 0usize;
+    let mut first: Option<(String, String)> = None;
+    println!("BOX\tFAMILY\tPACKAGE\tVERSION\tSUMMARY");
+    for (name, fam, res) in &results {
+        match res {
+            Ok(hits) => {
+                for h in hits {
+                    let version = if h.version.is_empty() { "-" } else { h.version.as_str() };
+                    println!("{}\t{}\t{}\t{}\t{}", name, format_family(*fam), h.package, version, h.summary);
+                    if first.is_none() { first = Some((name.clone(), h.package.clone())); }
+                }
+                total += hits.len();
+            }
+            Err(e) => eprintln!("Warning: searching '{}' failed: {:#}", name, e),
+        }
+    }
+    match first {
+        Some((bx, pkg)) => println!("\n{} result(s). Install one with: pkgbridge add --container {} {}", total, bx, pkg),
+        None => println!("No packages matched '{}'.", args.term),
+    }
+    Ok(())
 }
 
-fn to_names_only(bins: Vec<String>) -> Vec<String> { bins }
+fn add_pkg(cli: &Cli, arg: PkgArg) -> Result<()> {
+    let name = match (&cli.container, cli.family) {
+        (Some(c), _) => c.clone(),
+        (None, Some(fa)) => pm::show_defaults()?
+            .get(pm::family_key(to_family(fa)))
+            .cloned()
+            .ok_or_else(|| anyhow!("no default box for {}; pass --container", pm::family_key(to_family(fa))))?,
+        (None, None) => return Err(anyhow!("--container (or --family with a default box) is required for add")),
+    };
+    let fam = distro::classify_box_family(&name)?;
+    if held_pkgs(&name)?.contains(&arg.pkg) {
+        return Err(anyhow!("'{}' is held in '{}'; run `pkgbridge unhold {} {}` first", arg.pkg, name, name, arg.pkg));
+    }
+    let cmd = pkgops::install_names_cmd(fam, std::slice::from_ref(&arg.pkg));
+    snapshot_box(&name, Some(fam))?;
+    println!("Installing '{}' inside box '{}'...", arg.pkg, name);
+    if !distro::enter_status_elevated_inherit(&name, &cmd)? {
+        return Err(anyhow!("installing '{}' failed inside '{}'", arg.pkg, name));
+    }
+    if cli.no_export {
+        println!("--no-export: skipping export stage");
+        return Ok(());
+    }
+    post_transaction_box(&name, fam)?;
+    if !dryrun::enabled() { println!("Install completed."); }
+    Ok(())
+}
 
-fn desktop_cmd(cmd: DesktopCmd, dry_run: bool) -> Result<()> {
+fn which_cmd(cmd: &str) -> Result<()> {
+    let in_bindir = host_bin_dir().join(cmd);
+    let path = if in_bindir.exists() { in_bindir } else { which::which(cmd).map_err(|_| anyhow!("'{}' not found in {} or on PATH", cmd, host_bin_dir().display()))? };
+    println!("{}: {}", cmd, path.display());
+    let Some(entry) = hostfiles::inspect(&path) else {
+        println!("  method:  native (not a pkgbridge or distrobox launcher)");
+        return Ok(());
+    };
+    println!("  method:  {}", entry.kind.label());
+    if entry.kind == hostfiles::Kind::PmShim {
+        if entry.box_name != "(default)" {
+            println!("  box:     {} (pinned by shim)", entry.box_name);
+        } else {
+            match entry.family.as_deref().and_then(pm::family_from_key).map(pm::default_box).transpose()?.flatten() {
+                Some(b) => println!("  box:     {} (family default, resolved at run time)", b),
+                None => println!("  box:     none yet (offers to create one on first use)"),
+            }
+        }
+        if let Some(f) = &entry.family { println!("  family:  {}", f); }
+        return Ok(());
+    }
+    let fam = distro::classify_box_family(&entry.box_name).ok();
+    match fam {
+        Some(f) => println!("  box:     {} ({})", entry.box_name, format_family(f)),
+        None => println!("  box:     {} (unreachable)", entry.box_name),
+    }
+    let target = entry.target.clone().unwrap_or_else(|| cmd.to_string());
+    println!("  target:  {}", target);
+    let Some(fam) = fam else { return Ok(()); };
+    let owner = distro::enter_capture(&entry.box_name, &pkgops::owner_cmd(fam, &target), false)
+        .ok()
+        .filter(|o| o.status.success())
+        .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+        .filter(|s| !s.is_empty());
+    match owner {
+        Some(pkg) => {
+            let version = distro::enter_capture(&entry.box_name, &pkgops::version_cmd(fam, &pkg), false)
+                .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+                .unwrap_or_default();
+            println!("  package: {} {}", pkg, version);
+        }
+        None => println!("  package: (not owned by any package)"),
+    }
+    Ok(())
+}
+
+fn info_pkg(box_name: &str, pkg: &str) -> Result<()> {
+    let fam = distro::classify_box_family(box_name)?;
+    let out = distro::enter_capture(box_name, &pkgops::info_cmd(fam, pkg), false)?;
+    if !out.status.success() {
+        return Err(anyhow!("package '{}' not found in '{}'", pkg, box_name));
+    }
+    print!("{}", String::from_utf8_lossy(&out.stdout));
+    if held_pkgs(box_name)?.iter().any(|p| p == pkg) {
+        println!();
+        println!("Held: yes (release with `pkgbridge unhold {} {}`)", box_name, pkg);
+    }
+    let (bins, apps) = scan_installed_pkg(box_name, fam, pkg).unwrap_or_default();
+    println!();
+    println!("Exported items ({}):", box_name);
+    if bins.is_empty() && apps.is_empty() { println!("  (package ships no bins or desktop entries)"); }
+    let bin_dir = host_bin_dir();
+    for b in &bins {
+        let candidates = [bin_dir.join(b), bin_dir.join(format!("{}-{}", b, box_name))];
+        print_export_status("bin", b, box_name, &candidates);
+    }
+    let apps_dir = host_apps_dir();
+    for app in &apps {
+        let base = std::path::Path::new(app).file_name().and_then(|s| s.to_str()).unwrap_or(app);
+        let stem = base.trim_end_matches(".desktop");
+        // distrobox-export prefixes the box name; collision copies use <stem>.<box>.desktop
+        let candidates = [apps_dir.join(format!("{}-{}", box_name, base)), apps_dir.join(format!("{}.{}.desktop", stem, box_name)), apps_dir.join(base)];
+        print_export_status("app", base, box_name, &candidates);
+    }
+    Ok(())
+}
+
+/// Packages held in a box with `hold`.
+fn held_pkgs(box_name: &str) -> Result<Vec<String>> {
+    Ok(config::load_state()?.holds.get(box_name).cloned().unwrap_or_default())
+}
+
+fn hold_pkg(box_name: &str, pkg: &str) -> Result<()> {
+    let fam = distro::classify_box_family(box_name)?;
+    let cmd = pkgops::hold_cmd(fam, pkg);
+    if !distro::enter_status_elevated(box_name, &cmd)? {
+        return Err(anyhow!("holding '{}' failed inside '{}'", pkg, box_name));
+    }
+    let mut st = config::load_state()?;
+    let held = st.holds.entry(box_name.to_string()).or_default();
+    if !held.iter().any(|p| p == pkg) {
+        held.push(pkg.to_string());
+        config::save_state(&st)?;
+    }
+    if !dryrun::enabled() { println!("Held '{}' in '{}'; upgrades will keep the installed version.", pkg, box_name); }
+    Ok(())
+}
+
+fn unhold_pkg(box_name: &str, pkg: &str) -> Result<()> {
+    let fam = distro::classify_box_family(box_name)?;
+    let cmd = pkgops::unhold_cmd(fam, pkg);
+    if !distro::enter_status_elevated(box_name, &cmd)? {
+        return Err(anyhow!("releasing the hold on '{}' failed inside '{}'", pkg, box_name));
+    }
+    let mut st = config::load_state()?;
+    if let Some(held) = st.holds.get_mut(box_name) {
+        held.retain(|p| p != pkg);
+        if held.is_empty() { st.holds.remove(box_name); }
+        config::save_state(&st)?;
+    }
+    if !dryrun::enabled() { println!("Released the hold on '{}' in '{}'.", pkg, box_name); }
+    Ok(())
+}
+
+fn print_export_status(kind: &str, name: &str, box_name: &str, candidates: &[PathBuf]) {
+    let found = candidates.iter().find_map(|p| hostfiles::inspect(p).filter(|e| e.box_name == box_name));
+    match found {
+        Some(e) => println!("  {} {}: {} ({})", kind, name, e.path.display(), e.kind.label()),
+        None => println!("  {} {}: not exported", kind, name),
+    }
+}
+
+fn inspect_file(path: &std::path::Path, cli: &Cli) -> Result<()> {
+    if !path.exists() {
+        return Err(anyhow!("file does not exist: {}", path.display()));
+    }
+    let fmt = detect_package_format(path).context("detecting package format")?;
+    let meta = pkgmeta::read_package(path, fmt)?;
+    println!("File:       {}", path.display());
+    println!("Format:     {}", match fmt { PackageFormat::Deb => "deb", PackageFormat::Rpm => "rpm" });
+    println!("Package:    {} {} ({})", meta.name, meta.version, meta.arch);
+    println!("SHA-256:    {}", verify::sha256_file(path)?);
+    if !meta.maintainer.is_empty() { println!("Maintainer: {}", meta.maintainer); }
+    if !meta.summary.is_empty() { println!("Summary:    {}", meta.summary); }
+    match verify::verify_package(path, fmt) {
+        Ok(verify::SigStatus::Good { fingerprint, method }) => println!("Signature:  good {} from {}", method, fingerprint),
+        Ok(verify::SigStatus::UnknownKey { key_id, method }) => println!("Signature:  {} by untrusted key {}", method, key_id),
+        Ok(verify::SigStatus::Bad { method }) => println!("Signature:  BAD {}", method),
+        Ok(verify::SigStatus::Rejected { reason, method }) => println!("Signature:  {} not accepted ({})", method, reason),
+        Ok(verify::SigStatus::Unsigned) => println!("Signature:  none"),
+        Err(e) => println!("Signature:  not checked ({:#})", e),
+    }
+    println!();
+    println!("Dependencies ({}):", meta.depends.len());
+    for d in &meta.depends { println!("  {}", d); }
+    println!();
+    println!("Maintainer scripts ({}):", meta.scripts.len());
+    for f in pkgmeta::script_findings(&pkgmeta::executable_scripts(&meta)) { println!("  - {}", f); }
+    for s in &meta.scripts {
+        println!("  --- {} ---", s.heading());
+        for line in s.body.lines() { println!("  {}", line); }
+    }
+    println!();
+    println!("Files ({}):", meta.files.len());
+    for f in &meta.files { println!("  {}", f); }
+    let (bins, apps) = pkgmeta::exportable(&meta.files);
+    println!();
+    println!("Would export bins: {}", if bins.is_empty() { "(none)".to_string() } else { bins.join(", ") });
+    println!("Would export apps: {}", if apps.is_empty() { "(none)".to_string() } else { apps.join(", ") });
+    let boxes = distro::discover_boxes().unwrap_or_default();
+    println!("Target box: {}", preview_selection(&boxes, &fmt, cli)?);
+    Ok(())
+}
+
+fn keys_cmd(cmd: KeysCmd) -> Result<()> {
     match cmd {
-        DesktopCmd::Install => desktop::install(dry_run),
-        DesktopCmd::Uninstall => desktop::uninstall(dry_run),
+        KeysCmd::Add { file } => verify::add_key(&file),
+        KeysCmd::List => {
+            let keys = verify::list_keys()?;
+            if keys.is_empty() { println!("No trusted keys."); }
+            for (fpr, uid) in keys { println!("{}\t{}", fpr, uid); }
+            Ok(())
+        }
+        KeysCmd::Rm { fingerprint } => verify::remove_key(&fingerprint),
     }
 }
 
+fn repo_cmd(cmd: RepoCmd, cli: &Cli) -> Result<()> {
+    let box_name = cli.container.clone()
+        .or(cli.family.map(|f| pm::default_box(to_family(f))).transpose()?.flatten())
+        .ok_or_else(|| anyhow!("pass --container <box> (or --family with a default box set)"))?;
+    match cmd {
+        RepoCmd::Add { spec, key, name } => {
+            let fam = distro::classify_box_family(&box_name)?;
+            let rec = repos::add(&box_name, fam, &spec, key.as_deref(), name.as_deref())?;
+            if !dryrun::enabled() { println!("Added repository '{}' to '{}'.", rec.name, box_name); }
+            Ok(())
+        }
+        RepoCmd::List => {
+            let recs = repos::list(&box_name)?;
+            if recs.is_empty() { println!("No repositories added to '{}' by pkgbridge.", box_name); }
+            for r in recs { println!("{}\t{}\t{}", r.name, r.spec, r.key.as_deref().unwrap_or("-")); }
+            Ok(())
+        }
+        RepoCmd::Rm { repo } => {
+            let fam = distro::classify_box_family(&box_name)?;
+            let rec = repos::remove(&box_name, fam, &repo)?;
+            if !dryrun::enabled() { println!("Removed repository '{}' from '{}'.", rec.name, box_name); }
+            Ok(())
+        }
+    }
+}
+
+fn undo_host_changes() -> Result<()> {
+    let files = pm::undo_bindir_on_path()?;
+    let mimes = desktop::restore_mimeapps_defaults()?;
+    if files.is_empty() && mimes.is_empty() {
+        println!("No pkgbridge edits to ~/.profile, fish conf.d or mimeapps.list found.");
+        return Ok(());
+    }
+    if dryrun::enabled() { return Ok(()); }
+    for f in &files { println!("Removed pkgbridge PATH setup from {}", f.display()); }
+    if !mimes.is_empty() {
+        println!("Reverted {} default(s) in {}: {}", mimes.len(), desktop::mimeapps_path().display(), mimes.join(", "));
+    }
+    println!("Shims and the desktop entry stay; remove them with `pkgbridge pm remove-shims` and `pkgbridge desktop uninstall`.");
+    Ok(())
+}
+
 fn notify(summary: &str, body: &str) {
     if which::which("notify-send").is_ok() {
         let _ = std::process::Command::new("notify-send").args([summary, body]).status();
     }
 }
 
-fn maybe_first_run_prompt() {
+fn maybe_first_run_prompt() -> Result<()> {
     use std::io::{self, Write};
-    let mut st = config::load_state();
-    if st.first_run_done { return; }
+    let mut st = config::load_state()?;
+    if st.first_run_done { return Ok(()); }
     // Only prompt in interactive terminals
     if !std::io::stdin().is_terminal() || !std::io::stdout().is_terminal() {
         // Defer onboarding to first interactive run
-        return;
+        return Ok(());
     }
-    let boxes = match distro::discover_boxes() { Ok(b) => b, Err(_) => vec![] };
-    if boxes.is_empty() { st.first_run_done = true; let _ = config::save_state(&st); return; }
+    let boxes = distro::discover_boxes().unwrap_or_default();
+    if boxes.is_empty() { st.first_run_done = true; let _ = config::save_state(&st); return Ok(()); }
     // Determine families present and select first box per family for defaults
     let mut fam_to_box: std::collections::HashMap<BoxFamily, String> = std::collections::HashMap::new();
     let mut total_apps = 0usize;
@@ -908,7 +2024,7 @@ fn maybe_first_run_prompt() {
             }
         }
     }
-    if fam_to_box.is_empty() && total_apps == 0 { st.first_run_done = true; let _ = config::save_state(&st); return; }
+    if fam_to_box.is_empty() && total_apps == 0 { st.first_run_done = true; let _ = config::save_state(&st); return Ok(()); }
     println!("pkgbridge first-run setup:");
     let fam_list: Vec<&'static str> = fam_to_box.keys().map(|&f| format_family(f)).collect();
     if !fam_list.is_empty() { println!("- Found families: {}", fam_list.join(", ")); }
@@ -920,11 +2036,9 @@ fn maybe_first_run_prompt() {
     let ans = buf.trim().to_ascii_lowercase();
     if ans.is_empty() || ans == "y" || ans == "yes" {
         // Save defaults from fam_to_box
-        let mut cfg = config::load_config();
+        let mut cfg = config::load_config()?;
         for (fam, bx) in fam_to_box.iter() { cfg.pm_defaults.insert(pm::family_key(*fam).into(), bx.clone()); }
         let _ = config::save_config(&cfg);
-        // Generate shims
-        let _ = pm::generate_shims();
         // Export apps
         for b in &boxes {
             // Enumerate apps and export
@@ -942,7 +2056,8 @@ fn maybe_first_run_prompt() {
         }
         println!("First-run export completed.");
     }
-    // Also ensure bootstrap shims exist for missing host managers (apt/dnf/zypper/pacman)
-    let _ = pm::generate_bootstrap_shims();
+    // Shims for managers the host lacks offer to create a box on first use, so write them either way
+    let _ = pm::generate_shims();
     st.first_run_done = true; let _ = config::save_state(&st);
+    Ok(())
 }
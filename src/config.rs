@@ -4,16 +4,129 @@ use std::collections::HashMap;
 use std::fs;
 use std::path::PathBuf;
 
+use crate::dryrun;
+
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct Config {
     #[serde(default)]
     pub pm_defaults: HashMap<String, String>, // family -> box_name
+    #[serde(default)]
+    pub templates: HashMap<String, BoxTemplate>, // family -> template for newly created boxes
+    #[serde(default)]
+    pub scripts: ScriptPolicy,
+    /// Refuse packages without a good signature from a trusted key
+    #[serde(default)]
+    pub require_signed: bool,
+    /// Keep installed packages in /var/cache/pkgbridge inside the box instead of deleting them
+    #[serde(default)]
+    pub keep_packages: bool,
+    /// Let the box user run the family's package managers through sudo without a password
+    #[serde(default)]
+    pub nopasswd_pm: bool,
+    /// AUR helper built into Arch boxes pkgbridge creates or provisions
+    #[serde(default)]
+    pub aur_helper: Option<AurHelper>,
+}
+
+/// AUR helpers pkgbridge can build inside Arch boxes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AurHelper {
+    Yay,
+    Paru,
+}
+
+impl AurHelper {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name { "yay" => Some(Self::Yay), "paru" => Some(Self::Paru), _ => None }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self { Self::Yay => "yay", Self::Paru => "paru" }
+    }
+}
+
+/// What to do with packages that ship maintainer scripts (deb) or scriptlets (rpm).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScriptPolicy {
+    /// Show a summary and install
+    #[default]
+    Allow,
+    /// Ask before installing; refuse when there is no terminal to ask on
+    Prompt,
+    /// Refuse to install
+    Deny,
+}
+
+/// How pkgbridge sets up a box it creates for a family.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct BoxTemplate {
+    /// Image to use instead of the built-in default for the family
+    #[serde(default)]
+    pub image: Option<String>,
+    /// Steps run as root right after creation, in order
+    #[serde(default)]
+    pub provision: Vec<ProvisionStep>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProvisionStep {
+    /// Stable identifier used to record the step as applied; derived from the step itself if unset
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(flatten)]
+    pub action: ProvisionAction,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ProvisionAction {
+    /// Shell snippet run with `sh -lc`
+    Run { script: String },
+    /// Host file copied to `dest` inside the box
+    Copy { src: PathBuf, dest: String, #[serde(default)] mode: Option<String> },
+    /// Packages installed with the family's package manager
+    Packages { names: Vec<String> },
 }
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct State {
     #[serde(default)]
     pub first_run_done: bool,
+    #[serde(default)]
+    pub provisioned: HashMap<String, Vec<String>>, // box_name -> applied provisioning step ids
+    #[serde(default)]
+    pub staged: HashMap<String, Vec<String>>, // box_name -> package files copied into the box and not yet removed
+    #[serde(default)]
+    pub shims: HashMap<String, ShimRecord>, // shim path -> what pkgbridge wrote there
+    #[serde(default)]
+    pub repos: HashMap<String, Vec<RepoRecord>>, // box_name -> repositories added with `repo add`
+    #[serde(default)]
+    pub holds: HashMap<String, Vec<String>>, // box_name -> packages held with `hold`
+    #[serde(default)]
+    pub mime_previous: HashMap<String, String>, // MIME type -> default application before pkgbridge took it over
+}
+
+/// A third-party repository added to a box, kept so `box rebuild` can add it again.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RepoRecord {
+    pub name: String,
+    pub spec: String,
+    /// Signing key as given: host path, URL or fingerprint
+    #[serde(default)]
+    pub key: Option<String>,
+}
+
+/// Manifest entry for a package-manager shim written by pkgbridge.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShimRecord {
+    pub manager: String,
+    pub family: String,
+    /// Box pinned by older shims; absent for shims that resolve the default at run time
+    #[serde(default)]
+    pub box_name: Option<String>,
+    pub checksum: String,
 }
 
 pub fn config_dir() -> PathBuf {
@@ -30,38 +143,53 @@ pub fn state_dir() -> PathBuf {
     }).join("pkgbridge")
 }
 
-pub fn load_config() -> Config {
+/// A missing file means defaults; an unreadable or malformed one is an error rather than
+/// silently falling back to defaults.
+pub fn load_config() -> Result<Config> {
     let path = config_dir().join("config.toml");
     match fs::read_to_string(&path) {
-        Ok(s) => toml::from_str(&s).unwrap_or_default(),
-        Err(_) => Config::default(),
+        Ok(s) => toml::from_str(&s).with_context(|| format!("parsing {}", path.display())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Config::default()),
+        Err(e) => Err(e).with_context(|| format!("reading {}", path.display())),
     }
 }
 
 pub fn save_config(cfg: &Config) -> Result<()> {
     let dir = config_dir();
-    fs::create_dir_all(&dir).ok();
+    dryrun::create_dir_all(&dir).ok();
     let path = dir.join("config.toml");
     let s = toml::to_string_pretty(cfg).unwrap_or_default();
-    fs::write(&path, s).with_context(|| format!("writing {}", path.display()))
+    dryrun::write(&path, s).with_context(|| format!("writing {}", path.display()))
 }
 
-pub fn load_state() -> State {
+/// A missing file means defaults; an unreadable or malformed one is an error rather than
+/// silently falling back to defaults.
+pub fn load_state() -> Result<State> {
     let path = state_dir().join("state.toml");
     match fs::read_to_string(&path) {
-        Ok(s) => toml::from_str(&s).unwrap_or_default(),
-        Err(_) => State::default(),
+        Ok(s) => toml::from_str(&s).with_context(|| format!("parsing {}", path.display())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(State::default()),
+        Err(e) => Err(e).with_context(|| format!("reading {}", path.display())),
     }
 }
 
 pub fn save_state(st: &State) -> Result<()> {
     let dir = state_dir();
-    fs::create_dir_all(&dir).ok();
+    dryrun::create_dir_all(&dir).ok();
     let path = dir.join("state.toml");
     let s = toml::to_string_pretty(st).unwrap_or_default();
-    fs::write(&path, s).with_context(|| format!("writing {}", path.display()))
+    dryrun::write(&path, s).with_context(|| format!("writing {}", path.display()))
 }
 
+/// GnuPG home holding the keys trusted for package signatures.
+pub fn keys_dir() -> PathBuf { config_dir().join("keys") }
+
 pub fn snapshot_dir() -> PathBuf { state_dir().join("snapshots") }
 
+/// Append-only log of host paths pkgbridge changed (see `journal`).
+pub fn journal_path() -> PathBuf { state_dir().join("journal.log") }
+
 pub fn snapshot_path(container: &str) -> PathBuf { snapshot_dir().join(format!("{}.txt", container)) }
+
+/// Copies of locally installed packages, as `archive/<box>/<package>/<version>/<file>`.
+pub fn archive_dir() -> PathBuf { state_dir().join("archive") }
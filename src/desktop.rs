@@ -1,7 +1,9 @@
-use anyhow::{anyhow, Context, Result};
+use anyhow::{Context, Result};
 use std::fs;
-use std::io::Write;
 use std::path::PathBuf;
+use crate::config;
+use crate::dryrun;
+use crate::quote;
 
 pub fn desktop_dir() -> PathBuf {
     std::env::var("XDG_DATA_HOME").map(PathBuf::from).unwrap_or_else(|_| {
@@ -14,16 +16,12 @@ pub fn desktop_file_path() -> PathBuf {
     desktop_dir().join("pkgbridge.desktop")
 }
 
-pub fn install(dry_run: bool) -> Result<()> {
+pub fn install() -> Result<()> {
     let dir = desktop_dir();
     let path = desktop_file_path();
     let content = desktop_file_content();
-    if dry_run {
-        println!("--dry-run: would write {}", path.display());
-    } else {
-        fs::create_dir_all(&dir).ok();
-        fs::write(&path, content).with_context(|| format!("writing {}", path.display()))?;
-    }
+    dryrun::create_dir_all(&dir).ok();
+    dryrun::write(&path, content).with_context(|| format!("writing {}", path.display()))?;
     // Register MIME associations
     let mimes = vec![
         "application/vnd.debian.binary-package",
@@ -32,6 +30,8 @@ pub fn install(dry_run: bool) -> Result<()> {
         "application/x-redhat-package-manager",
     ];
     let _ = try_run("update-desktop-database", &[dir.to_string_lossy().as_ref()]);
+    // xdg-mime overwrites the current defaults, so note them first for `undo-host-changes`
+    remember_mime_defaults(&mimes)?;
     for mt in &mimes {
         let _ = try_run("xdg-mime", &["default", "pkgbridge.desktop", mt]);
     }
@@ -40,22 +40,22 @@ pub fn install(dry_run: bool) -> Result<()> {
     // Provide local MIME globs in case system DB is missing entries
     install_mime_xml()?;
     // Install app icon into XDG icon theme (hicolor)
-    install_icon(dry_run)?;
+    install_icon()?;
     // Update icon cache if available
     let _ = try_run("gtk-update-icon-cache", &[xdg_data_home().join("icons/hicolor").to_string_lossy().as_ref(), "-q"]);    
     let _ = try_run("update-mime-database", &[xdg_data_home().join("mime").to_string_lossy().as_ref()]);
     Ok(())
 }
 
-pub fn uninstall(_dry_run: bool) -> Result<()> {
+pub fn uninstall() -> Result<()> {
     let path = desktop_file_path();
-    let _ = std::fs::remove_file(&path);
+    if path.exists() { let _ = dryrun::remove_file(&path); }
     // Remove installed icon
     uninstall_icon().ok();
     let _ = try_run("gtk-update-icon-cache", &[xdg_data_home().join("icons/hicolor").to_string_lossy().as_ref(), "-q"]);
     let _ = try_run("update-desktop-database", &[desktop_dir().to_string_lossy().as_ref()]);
-    // Remove mimeapps defaults that point to pkgbridge.desktop
-    remove_mimeapps_defaults()?;
+    // Hand the MIME types back to the applications that had them
+    restore_mimeapps_defaults()?;
     Ok(())
 }
 
@@ -63,7 +63,7 @@ pub fn desktop_file_content() -> String {
     // Minimal .desktop to handle opening .deb/.rpm
     // Prefer absolute Exec/TryExec to work even if ~/.local/bin isn't on PATH
     let exec_bin = resolve_exec_bin();
-    let exec = format!("{} open %f", exec_bin);
+    let exec = format!("{} open %f", quote::desktop_exec_arg(&exec_bin));
     let mut s = String::new();
     s.push_str("[Desktop Entry]\n");
     s.push_str("Type=Application\n");
@@ -98,17 +98,48 @@ fn resolve_exec_bin() -> String {
 }
 
 fn try_run(cmd: &str, args: &[&str]) -> Result<()> {
-    let status = std::process::Command::new(cmd).args(args).status();
+    let status = dryrun::status(std::process::Command::new(cmd).args(args));
     match status {
         Ok(_) => Ok(()),
         Err(_) => Ok(()),
     }
 }
 
+pub fn mimeapps_path() -> PathBuf {
+    xdg_config_home().join("mimeapps.list")
+}
+
+/// `key=value` entries of the `[Default Applications]` section.
+fn default_apps(data: &str) -> Vec<(String, String)> {
+    let mut in_section = false;
+    let mut out = Vec::new();
+    for l in data.lines() {
+        if l.starts_with('[') { in_section = l.trim() == "[Default Applications]"; continue; }
+        if !in_section { continue; }
+        if let Some((k, v)) = l.split_once('=') { out.push((k.trim().to_string(), v.trim().to_string())); }
+    }
+    out
+}
+
+/// Record in state which application each of `mimes` defaults to, unless that is already pkgbridge.
+fn remember_mime_defaults(mimes: &[&str]) -> Result<()> {
+    let Ok(data) = fs::read_to_string(mimeapps_path()) else { return Ok(()); };
+    let mut st = config::load_state()?;
+    let mut changed = false;
+    for (k, v) in default_apps(&data) {
+        if mimes.contains(&k.as_str()) && !v.contains("pkgbridge.desktop") && st.mime_previous.get(&k) != Some(&v) {
+            st.mime_previous.insert(k, v);
+            changed = true;
+        }
+    }
+    if changed { config::save_state(&st)?; }
+    Ok(())
+}
+
 fn ensure_mimeapps_defaults(mimes: &[&str]) -> Result<()> {
     let cfg_dir = xdg_config_home();
-    fs::create_dir_all(&cfg_dir).ok();
-    let path = cfg_dir.join("mimeapps.list");
+    dryrun::create_dir_all(&cfg_dir).ok();
+    let path = mimeapps_path();
     let mut data = String::new();
     if let Ok(s) = fs::read_to_string(&path) { data = s; }
     let mut lines: Vec<String> = if data.is_empty() { vec![] } else { data.lines().map(|s| s.to_string()).collect() };
@@ -116,7 +147,7 @@ fn ensure_mimeapps_defaults(mimes: &[&str]) -> Result<()> {
     let mut idx = lines.iter().position(|l| l.trim() == "[Default Applications]");
     if idx.is_none() { lines.push("[Default Applications]".into()); idx = Some(lines.len()-1); lines.push(String::new()); }
     // Map of mime->line index under the section
-    let mut i = idx.unwrap() + 1;
+    let i = idx.unwrap() + 1;
     let mut end = lines.len();
     for (j, l) in lines.iter().enumerate().skip(i) { if l.starts_with('[') { end = j; break; } }
     // Build a set of existing entries
@@ -126,41 +157,51 @@ fn ensure_mimeapps_defaults(mimes: &[&str]) -> Result<()> {
     }
     for mt in mimes {
         let entry = format!("{}=pkgbridge.desktop;", mt);
-        if let Some(&j) = existing.get(&mt.to_string()) {
+        if let Some(&j) = existing.get(*mt) {
             lines[j] = entry;
         } else {
             lines.insert(end, entry);
             end += 1;
         }
     }
-    let mut out = fs::File::create(&path).with_context(|| format!("writing {}", path.display()))?;
-    for l in &lines { writeln!(out, "{}", l).ok(); }
+    let content: String = lines.iter().map(|l| format!("{}\n", l)).collect();
+    dryrun::write(&path, content).with_context(|| format!("writing {}", path.display()))?;
     Ok(())
 }
 
-fn remove_mimeapps_defaults() -> Result<()> {
-    let cfg_dir = xdg_config_home();
-    let path = cfg_dir.join("mimeapps.list");
-    let Ok(s) = fs::read_to_string(&path) else { return Ok(()); };
+/// Replace defaults pointing at pkgbridge.desktop with the ones recorded before install, or drop them.
+/// Returns the MIME types that changed.
+pub fn restore_mimeapps_defaults() -> Result<Vec<String>> {
+    let path = mimeapps_path();
+    let Ok(s) = fs::read_to_string(&path) else { return Ok(vec![]); };
     let mut lines: Vec<String> = s.lines().map(|x| x.to_string()).collect();
-    let mut i = match lines.iter().position(|l| l.trim() == "[Default Applications]") { Some(v) => v + 1, None => return Ok(()) };
+    let i = match lines.iter().position(|l| l.trim() == "[Default Applications]") { Some(v) => v + 1, None => return Ok(vec![]) };
     let mut end = lines.len();
     for (j, l) in lines.iter().enumerate().skip(i) { if l.starts_with('[') { end = j; break; } }
+    let mut st = config::load_state()?;
+    let mut touched: Vec<String> = Vec::new();
     let mut kept: Vec<String> = Vec::new();
     kept.extend(lines.drain(..i));
     for l in lines.drain(..end-i) {
-        if l.contains("=pkgbridge.desktop;") { continue; }
+        if l.contains("=pkgbridge.desktop;") {
+            let mt = l.split_once('=').map(|(k, _)| k.trim().to_string()).unwrap_or_default();
+            if let Some(prev) = st.mime_previous.remove(&mt) { kept.push(format!("{}={}", mt, prev)); }
+            touched.push(mt);
+            continue;
+        }
         kept.push(l);
     }
+    if touched.is_empty() { return Ok(touched); }
     kept.extend(lines);
-    let mut out = fs::File::create(&path).with_context(|| format!("writing {}", path.display()))?;
-    for l in &kept { writeln!(out, "{}", l).ok(); }
-    Ok(())
+    let content: String = kept.iter().map(|l| format!("{}\n", l)).collect();
+    dryrun::write(&path, content).with_context(|| format!("writing {}", path.display()))?;
+    config::save_state(&st)?;
+    Ok(touched)
 }
 
 fn install_mime_xml() -> Result<()> {
     let base = xdg_data_home().join("mime").join("packages");
-    fs::create_dir_all(&base).ok();
+    dryrun::create_dir_all(&base).ok();
     let path = base.join("pkgbridge.xml");
     let content = r#"<?xml version="1.0" encoding="UTF-8"?>
 <mime-info xmlns="http://www.freedesktop.org/standards/shared-mime-info">
@@ -175,7 +216,7 @@ fn install_mime_xml() -> Result<()> {
   </mime-type>
 </mime-info>
 "#;
-    fs::write(&path, content).with_context(|| format!("writing {}", path.display()))?;
+    dryrun::write(&path, content).with_context(|| format!("writing {}", path.display()))?;
     Ok(())
 }
 
@@ -188,21 +229,17 @@ fn icon_target_dir() -> PathBuf {
 
 fn icon_target_path() -> PathBuf { icon_target_dir().join("pkgbridge.png") }
 
-fn install_icon(dry_run: bool) -> Result<()> {
+fn install_icon() -> Result<()> {
     let dir = icon_target_dir();
     let path = icon_target_path();
-    if dry_run {
-        println!("--dry-run: would install icon to {}", path.display());
-        return Ok(());
-    }
-    std::fs::create_dir_all(&dir).ok();
-    std::fs::write(&path, ICON_BYTES).with_context(|| format!("writing {}", path.display()))?;
+    dryrun::create_dir_all(&dir).ok();
+    dryrun::write(&path, ICON_BYTES).with_context(|| format!("writing {}", path.display()))?;
     Ok(())
 }
 
 fn uninstall_icon() -> Result<()> {
     let path = icon_target_path();
-    if path.exists() { let _ = std::fs::remove_file(&path); }
+    if path.exists() { let _ = dryrun::remove_file(&path); }
     Ok(())
 }
 
@@ -1,8 +1,20 @@
 use anyhow::{anyhow, Context, Result};
 use serde::Deserialize;
-use std::io::Write;
+use std::io::{IsTerminal, Read, Write};
 use std::process::{Command, Stdio};
 
+use crate::dryrun;
+
+/// Where packages are copied inside a box before installing them.
+pub const STAGING_DIR: &str = "/tmp/pkgbridge";
+/// Where installed packages are kept inside a box when `keep_packages` is set.
+pub const PACKAGE_CACHE_DIR: &str = "/var/cache/pkgbridge";
+
+/// Chunk size used when streaming files into a box.
+const COPY_CHUNK: usize = 1 << 20;
+/// Files at least this large show copy progress.
+const PROGRESS_THRESHOLD: u64 = 64 << 20;
+
 #[derive(Debug, Clone, Default)]
 pub struct DistroBox {
     pub name: String,
@@ -16,6 +28,9 @@ pub enum Family {
     Fedora,
     OpenSuse,
     Arch,
+    Alpine,
+    Void,
+    Gentoo,
 }
 
 /// Try to discover existing Distrobox containers.
@@ -93,7 +108,7 @@ fn parse_boxes_plain(s: &str) -> Vec<DistroBox> {
         if t.contains('|') {
             // Pipe-separated table
             // Split and trim columns
-            let mut cols: Vec<String> = t.split('|').map(|c| c.trim().to_string()).collect();
+            let cols: Vec<String> = t.split('|').map(|c| c.trim().to_string()).collect();
             // Skip header row
             if cols.iter().any(|c| c.eq_ignore_ascii_case("NAME")) && cols.iter().any(|c| c.eq_ignore_ascii_case("ID")) {
                 saw_pipe_header = true;
@@ -115,9 +130,9 @@ fn parse_boxes_plain(s: &str) -> Vec<DistroBox> {
             continue;
         }
         let parts: Vec<&str> = t.split_whitespace().collect();
-        if parts.len() >= 1 {
+        if !parts.is_empty() {
             // If we saw a pipe header earlier, the first column here is likely ID; skip such lines
-            if saw_pipe_header && parts.get(0).map(|c| c.len()).unwrap_or(0) >= 6 && parts.get(1).is_some() {
+            if saw_pipe_header && parts.first().map(|c| c.len()).unwrap_or(0) >= 6 && parts.get(1).is_some() {
                 // Likely an ID then NAME; take NAME
                 let name = parts.get(1).unwrap().to_string();
                 let image = parts.get(3).map(|s| s.to_string());
@@ -133,6 +148,21 @@ fn parse_boxes_plain(s: &str) -> Vec<DistroBox> {
     boxes
 }
 
+/// Best-effort family guess from an image reference, for callers that must not enter the box.
+pub fn guess_family_from_image(image: &str) -> Option<Family> {
+    let lower = image.to_ascii_lowercase();
+    let tokens: Vec<&str> = lower.split(|c: char| !c.is_ascii_alphanumeric()).filter(|t| !t.is_empty()).collect();
+    let has = |k: &str| tokens.contains(&k);
+    if has("debian") || has("ubuntu") { return Some(Family::Debian); }
+    if has("fedora") || has("rhel") || has("centos") || has("rockylinux") || has("almalinux") || has("ubi9") || has("ubi8") { return Some(Family::Fedora); }
+    if has("opensuse") || has("suse") || has("tumbleweed") || has("leap") { return Some(Family::OpenSuse); }
+    if has("archlinux") || has("arch") || has("manjaro") { return Some(Family::Arch); }
+    if has("alpine") { return Some(Family::Alpine); }
+    if has("voidlinux") || has("void") { return Some(Family::Void); }
+    if has("gentoo") || has("stage3") { return Some(Family::Gentoo); }
+    None
+}
+
 /// Classify a Distrobox into a Linux distribution family by reading /etc/os-release inside it.
 pub fn classify_box_family(name: &str) -> Result<Family> {
     let out = Command::new("distrobox")
@@ -170,23 +200,31 @@ fn unquote(s: &str) -> String {
     } else { t.to_string() }
 }
 
-fn classify_ids(id: &Option<String>, id_like: &Vec<String>) -> Option<Family> {
+fn classify_ids(id: &Option<String>, id_like: &[String]) -> Option<Family> {
     let mut tokens: Vec<String> = Vec::new();
     if let Some(i) = id { tokens.push(i.clone()); }
-    tokens.extend(id_like.clone());
+    tokens.extend(id_like.iter().cloned());
     let has = |k: &str| tokens.iter().any(|t| t == k);
     if has("debian") || has("ubuntu") { return Some(Family::Debian); }
     if has("fedora") || has("rhel") || has("centos") { return Some(Family::Fedora); }
     if has("opensuse") || has("sles") || has("suse") { return Some(Family::OpenSuse); }
     if has("arch") || has("manjaro") || has("endeavouros") { return Some(Family::Arch); }
+    if has("alpine") || has("postmarketos") { return Some(Family::Alpine); }
+    if has("void") { return Some(Family::Void); }
+    if has("gentoo") || has("funtoo") { return Some(Family::Gentoo); }
     None
 }
 
 /// Create a distrobox with the given name and image.
 pub fn create_box(name: &str, image: &str) -> Result<()> {
-    let status = Command::new("distrobox")
+    create_box_with(name, image, &CreateOptions::default())
+}
+
+/// Create a box passing the given creation options to `distrobox create`.
+pub fn create_box_with(name: &str, image: &str, opts: &CreateOptions) -> Result<()> {
+    let status = dryrun::status(Command::new("distrobox")
         .args(["create", "--name", name, "--image", image, "-Y", "--yes"]) // accept both variants
-        .status()
+        .args(opts.args()))
         .with_context(|| format!("creating distrobox {name} from {image}"))?;
     if !status.success() {
         return Err(anyhow!("distrobox create failed for {name}"));
@@ -194,6 +232,216 @@ pub fn create_box(name: &str, image: &str) -> Result<()> {
     Ok(())
 }
 
+/// Start a box by entering it with a no-op command.
+pub fn start_box(name: &str) -> Result<()> {
+    if !enter_status(name, "true", false)? {
+        return Err(anyhow!("failed to start box {name}"));
+    }
+    Ok(())
+}
+
+/// Stop a running box.
+pub fn stop_box(name: &str) -> Result<()> {
+    let status = dryrun::status(Command::new("distrobox").args(["stop", "--yes", name]))
+        .with_context(|| format!("stopping distrobox {name}"))?;
+    if !status.success() {
+        return Err(anyhow!("distrobox stop failed for {name}"));
+    }
+    Ok(())
+}
+
+/// Remove a box, stopping it first if it is running.
+pub fn remove_box(name: &str) -> Result<()> {
+    let status = dryrun::status(Command::new("distrobox").args(["rm", "--force", "--yes", name]))
+        .with_context(|| format!("removing distrobox {name}"))?;
+    if !status.success() {
+        return Err(anyhow!("distrobox rm failed for {name}"));
+    }
+    Ok(())
+}
+
+/// Options a box was created with that `distrobox create` needs again to recreate it.
+#[derive(Debug, Clone, Default)]
+pub struct CreateOptions {
+    /// Custom `--home`, if any
+    pub home: Option<String>,
+    pub hostname: Option<String>,
+    pub init: bool,
+    pub nvidia: bool,
+    /// `--volume` mounts, as `src:dest[:opts]`
+    pub volumes: Vec<String>,
+    pub additional_packages: Vec<String>,
+    pub init_hooks: Option<String>,
+    pub pre_init_hooks: Option<String>,
+}
+
+impl CreateOptions {
+    fn args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        let mut pair = |flag: &str, value: &str| args.extend([flag.to_string(), value.to_string()]);
+        if let Some(h) = &self.home { pair("--home", h); }
+        if let Some(h) = &self.hostname { pair("--hostname", h); }
+        for v in &self.volumes { pair("--volume", v); }
+        if !self.additional_packages.is_empty() { pair("--additional-packages", &self.additional_packages.join(" ")); }
+        if let Some(h) = &self.init_hooks { pair("--init-hooks", h); }
+        if let Some(h) = &self.pre_init_hooks { pair("--pre-init-hooks", h); }
+        if self.init { args.push("--init".into()); }
+        if self.nvidia { args.push("--nvidia".into()); }
+        args
+    }
+}
+
+/// Container paths distrobox bind-mounts by itself; any other bind came from `--volume`.
+const DISTROBOX_MOUNTS: &[&str] = &[
+    "/tmp", "/run", "/dev", "/sys", "/media", "/mnt", "/var/home", "/var/mnt", "/var/log/journal",
+    "/etc/hosts", "/etc/hostname", "/etc/resolv.conf", "/etc/localtime",
+    "/usr/bin/entrypoint", "/usr/bin/distrobox-export", "/usr/bin/distrobox-host-exec",
+];
+
+/// Read the options a box was created with back from its container.
+/// Options given through `--additional-flags` (and the `--unshare-*` switches) cannot be recovered.
+pub fn create_options(name: &str) -> Result<CreateOptions> {
+    let manager = container_manager(name)?;
+    let out = Command::new(&manager)
+        .args(["inspect", "--type", "container", name])
+        .output()
+        .with_context(|| format!("running {manager} inspect"))?;
+    if !out.status.success() {
+        return Err(anyhow!("{manager} inspect failed for {name}"));
+    }
+    let parsed: serde_json::Value = serde_json::from_slice(&out.stdout).with_context(|| format!("parsing {manager} inspect output"))?;
+    let c = &parsed[0];
+    let strings = |v: &serde_json::Value| -> Vec<String> {
+        v.as_array().map(|a| a.iter().filter_map(|s| s.as_str().map(str::to_string)).collect()).unwrap_or_default()
+    };
+    // distrobox sets HOME to the custom home directory when one was given at creation
+    let host_home = std::env::var("HOME").unwrap_or_default();
+    let home = strings(&c["Config"]["Env"])
+        .iter()
+        .find_map(|l| l.strip_prefix("HOME="))
+        .map(|h| h.to_string())
+        .filter(|h| !h.is_empty() && *h != host_home);
+    // The rest is in the entrypoint arguments: `--init 1 --nvidia 0 ... -- <init hooks>`
+    let cmd = strings(&c["Config"]["Cmd"]);
+    let value = |flag: &str| cmd.iter().position(|a| a == flag).and_then(|i| cmd.get(i + 1)).filter(|v| !v.is_empty()).cloned();
+    let set = |flag: &str| matches!(value(flag).as_deref(), Some("1" | "true"));
+    let init_hooks = cmd.iter().position(|a| a == "--").and_then(|i| cmd.get(i + 1)).filter(|v| !v.is_empty()).cloned();
+    let volumes = strings(&c["HostConfig"]["Binds"])
+        .into_iter()
+        .filter(|b| {
+            let dest = b.split(':').nth(1).unwrap_or_default().trim_end_matches('/');
+            let own = |p: &str| dest == p || dest.starts_with(&format!("{p}/"));
+            !(DISTROBOX_MOUNTS.iter().any(|m| own(m)) || own(&host_home) || home.as_deref().is_some_and(own))
+        })
+        .collect();
+    Ok(CreateOptions {
+        hostname: c["Config"]["Hostname"].as_str().filter(|h| !h.is_empty()).map(str::to_string),
+        init: set("--init"),
+        nvidia: set("--nvidia"),
+        volumes,
+        additional_packages: value("--additional-packages").map(|p| p.split_whitespace().map(str::to_string).collect()).unwrap_or_default(),
+        init_hooks,
+        pre_init_hooks: value("--pre-init-hooks"),
+        home,
+    })
+}
+
+/// Image a box was committed to before a risky operation.
+#[derive(Debug, Clone)]
+pub struct Checkpoint {
+    pub box_name: String,
+    pub image: String,
+    manager: String,
+    /// How the box was created, so the restored one matches it
+    options: CreateOptions,
+}
+
+/// Container manager behind a box: the one `distrobox list` reported, else the one distrobox would pick.
+pub fn container_manager(name: &str) -> Result<String> {
+    if let Some(b) = discover_boxes().unwrap_or_default().into_iter().find(|b| b.name == name) {
+        if b.runtime == "podman" || b.runtime == "docker" { return Ok(b.runtime); }
+    }
+    if let Ok(m) = std::env::var("DBX_CONTAINER_MANAGER") {
+        if m == "podman" || m == "docker" { return Ok(m); }
+    }
+    ["podman", "docker"]
+        .into_iter()
+        .find(|m| which::which(m).is_ok())
+        .map(|m| m.to_string())
+        .ok_or_else(|| anyhow!("neither podman nor docker found"))
+}
+
+/// Commit the box's container to a local image.
+pub fn checkpoint_box(name: &str) -> Result<Checkpoint> {
+    let manager = container_manager(name)?;
+    let secs = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let image = format!("localhost/pkgbridge-checkpoint:{}-{}", name.to_ascii_lowercase(), secs);
+    if !dryrun::perform(format_args!("commit '{}' to {}", name, image)) {
+        return Ok(Checkpoint { box_name: name.to_string(), image, manager, options: CreateOptions::default() });
+    }
+    let status = Command::new(&manager)
+        .args(["commit", name, &image])
+        .stdout(Stdio::null())
+        .status()
+        .with_context(|| format!("running {manager} commit"))?;
+    if !status.success() {
+        return Err(anyhow!("{manager} commit failed for {name}"));
+    }
+    let options = create_options(name)?;
+    Ok(Checkpoint { box_name: name.to_string(), image, manager, options })
+}
+
+/// Replace the box with a new one created from the checkpoint image, under the same name and
+/// creation options. The new box is created under a temporary name and renamed once the old one
+/// is gone, so a failed create leaves the original box in place.
+pub fn restore_checkpoint(cp: &Checkpoint) -> Result<()> {
+    let temp = temp_box_name(&cp.box_name);
+    create_box_with(&temp, &cp.image, &cp.options)
+        .with_context(|| format!("'{}' was left unchanged", cp.box_name))?;
+    replace_box(&cp.box_name, &temp, &cp.manager)
+}
+
+/// Name a replacement box is built under before it takes over `name`.
+pub fn temp_box_name(name: &str) -> String {
+    format!("{}-pkgbridge-new", name)
+}
+
+/// Remove box `name` and rename box `temp` to take its place; a failure says how to finish by hand.
+pub fn replace_box(name: &str, temp: &str, manager: &str) -> Result<()> {
+    let rename = format!("{} rename {} {}", manager, temp, name);
+    remove_box(name)
+        .with_context(|| format!("the new box is '{}'; remove '{}' and run: {}", temp, name, rename))?;
+    let status = dryrun::status(Command::new(manager).args(["rename", temp, name]))
+        .with_context(|| format!("running {} rename", manager))?;
+    if !status.success() {
+        return Err(anyhow!("{} rename failed; the new box is '{}', finish with: {}", manager, temp, rename));
+    }
+    Ok(())
+}
+
+/// Delete a checkpoint image that is no longer needed.
+pub fn drop_checkpoint(cp: &Checkpoint) -> Result<()> {
+    let status = dryrun::status(Command::new(&cp.manager).args(["rmi", &cp.image]).stdout(Stdio::null()))
+        .with_context(|| format!("running {} rmi", cp.manager))?;
+    if !status.success() {
+        return Err(anyhow!("{} rmi failed for {}", cp.manager, cp.image));
+    }
+    Ok(())
+}
+
+/// Open an interactive login shell inside a box.
+pub fn enter_interactive(name: &str) -> Result<bool> {
+    if !dryrun::perform(format_args!("open a shell in '{}'", name)) { return Ok(true); }
+    let st = Command::new("distrobox")
+        .args(["enter", "-n", name])
+        .stdin(Stdio::inherit())
+        .stdout(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .status()
+        .with_context(|| format!("entering box {name}"))?;
+    Ok(st.success())
+}
+
 /// Run a command inside a distrobox and capture output
 pub fn enter_capture(name: &str, cmd: &str, as_root: bool) -> Result<std::process::Output> {
     let mut c = Command::new("distrobox");
@@ -208,6 +456,7 @@ pub fn enter_capture(name: &str, cmd: &str, as_root: bool) -> Result<std::proces
 
 /// Run a command inside a distrobox and return exit status only
 pub fn enter_status(name: &str, cmd: &str, as_root: bool) -> Result<bool> {
+    if !dryrun::perform(format_args!("run inside '{}'{}: {}", name, if as_root { " (root)" } else { "" }, cmd)) { return Ok(true); }
     // Use no-tty as well
     let mut c = Command::new("distrobox");
     c.arg("enter");
@@ -220,6 +469,7 @@ pub fn enter_status(name: &str, cmd: &str, as_root: bool) -> Result<bool> {
 
 /// Run a command inside a distrobox with inherited stdio (TTY forwarding)
 pub fn enter_status_inherit(name: &str, cmd: &str, as_root: bool) -> Result<bool> {
+    if !dryrun::perform(format_args!("run inside '{}'{}: {}", name, if as_root { " (root)" } else { "" }, cmd)) { return Ok(true); }
     let mut c = Command::new("distrobox");
     c.arg("enter");
     if as_root { c.arg("--root"); }
@@ -231,10 +481,120 @@ pub fn enter_status_inherit(name: &str, cmd: &str, as_root: bool) -> Result<bool
     Ok(st.success())
 }
 
-/// Copy a local file into the box at /tmp/pkgbridge/<sanitized-basename> via stdin piping.
+/// Exit code of the elevation wrapper when the box user has neither a usable `sudo` nor `doas`.
+const NO_ELEVATION: i32 = 125;
+
+/// Wrap `cmd` so the box user runs it as root through `sudo`, else `doas` (as-is when already root).
+/// Without `prompt` a helper that would ask for a password counts as unusable.
+fn elevation_wrapper(cmd: &str, prompt: bool) -> String {
+    let q = crate::quote::sh(cmd);
+    if prompt {
+        format!(
+            "if [ \"$(id -u)\" = 0 ]; then exec sh -lc {q}; \
+             elif command -v sudo >/dev/null; then exec sudo sh -lc {q}; \
+             elif command -v doas >/dev/null; then exec doas sh -lc {q}; \
+             else exit {NO_ELEVATION}; fi"
+        )
+    } else {
+        format!(
+            "if [ \"$(id -u)\" = 0 ]; then exec sh -lc {q}; \
+             elif command -v sudo >/dev/null && sudo -n true 2>/dev/null; then exec sudo -n sh -lc {q}; \
+             elif command -v doas >/dev/null && doas -n true 2>/dev/null; then exec doas -n sh -lc {q}; \
+             else exit {NO_ELEVATION}; fi"
+        )
+    }
+}
+
+/// `distrobox enter` running `cmd` through `sh -lc`.
+fn enter_command(name: &str, cmd: &str, as_root: bool, tty: bool) -> Command {
+    let mut c = Command::new("distrobox");
+    c.arg("enter");
+    if !tty { c.arg("--no-tty"); }
+    if as_root { c.arg("--root"); }
+    c.args(["-n", name, "--", "sh", "-lc", cmd]);
+    c
+}
+
+/// Run `cmd` as root inside a rootless box: as the box user through its `sudo`/`doas`, with
+/// `distrobox enter --root` (a rootful container) only as the fallback when neither is usable.
+/// `run` starts the prepared command and waits for it.
+fn run_elevated(name: &str, cmd: &str, tty: bool, run: impl Fn(&mut Command) -> std::io::Result<std::process::ExitStatus>) -> Result<bool> {
+    let prompt = tty && std::io::stdin().is_terminal();
+    let st = run(&mut enter_command(name, &elevation_wrapper(cmd, prompt), false, tty))
+        .with_context(|| format!("entering box {} to run (elevated): {}", name, cmd))?;
+    if st.code() != Some(NO_ELEVATION) { return Ok(st.success()); }
+    log::debug!("no usable sudo/doas in {}; retrying with --root", name);
+    let st = run(&mut enter_command(name, cmd, true, tty))
+        .with_context(|| format!("entering box {} to run (root): {}", name, cmd))?;
+    Ok(st.success())
+}
+
+/// Run a command as root inside a distrobox and return exit status only.
+pub fn enter_status_elevated(name: &str, cmd: &str) -> Result<bool> {
+    if !dryrun::perform(format_args!("run inside '{}' (root): {}", name, cmd)) { return Ok(true); }
+    run_elevated(name, cmd, false, |c| c.status())
+}
+
+/// Run a command as root inside a distrobox with inherited stdio; sudo may prompt on a terminal.
+pub fn enter_status_elevated_inherit(name: &str, cmd: &str) -> Result<bool> {
+    if !dryrun::perform(format_args!("run inside '{}' (root): {}", name, cmd)) { return Ok(true); }
+    run_elevated(name, cmd, true, |c| c.stdin(Stdio::inherit()).stdout(Stdio::inherit()).stderr(Stdio::inherit()).status())
+}
+
+/// Run a command as root inside a distrobox feeding `input` on stdin.
+pub fn enter_status_elevated_with_input(name: &str, cmd: &str, input: &[u8]) -> Result<bool> {
+    if !dryrun::perform(format_args!("run inside '{}' (root): {}", name, cmd)) { return Ok(true); }
+    run_elevated(name, cmd, false, |c| {
+        let mut child = c.stdin(Stdio::piped()).spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            // A command that exits without reading everything is reported through its status
+            let _ = stdin.write_all(input);
+        }
+        child.wait()
+    })
+}
+
+/// Run a read-only query as root inside a distrobox and capture its output.
+pub fn enter_capture_elevated(name: &str, cmd: &str) -> Result<std::process::Output> {
+    let out = enter_command(name, &elevation_wrapper(cmd, false), false, false)
+        .output()
+        .with_context(|| format!("entering box {} to run (elevated): {}", name, cmd))?;
+    if out.status.code() != Some(NO_ELEVATION) { return Ok(out); }
+    log::debug!("no usable sudo/doas in {}; retrying with --root", name);
+    enter_capture(name, cmd, true)
+}
+
+/// Run `argv` inside a distrobox as-is (no shell in between) with inherited stdio; returns the exit code.
+/// Not recorded under dry-run: it also runs read-only queries, so callers record mutating runs themselves.
+pub fn enter_argv(name: &str, argv: &[String]) -> Result<i32> {
+    let st = Command::new("distrobox")
+        .args(["enter", "-n", name, "--"])
+        .args(argv)
+        .stdin(Stdio::inherit())
+        .stdout(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .status()
+        .with_context(|| format!("entering box {} to run: {}", name, argv.join(" ")))?;
+    // Killed by a signal: report the conventional 128+n like a shell would
+    #[cfg(unix)]
+    if let Some(sig) = std::os::unix::process::ExitStatusExt::signal(&st) { return Ok(128 + sig); }
+    Ok(st.code().unwrap_or(1))
+}
+
+/// Privilege helper available to the box user: `sudo`, else `doas`, else none.
+pub fn elevation_helper(name: &str) -> Option<&'static str> {
+    let out = enter_capture(name, "if command -v sudo >/dev/null; then echo sudo; elif command -v doas >/dev/null; then echo doas; fi", false).ok()?;
+    match String::from_utf8_lossy(&out.stdout).trim() {
+        "sudo" => Some("sudo"),
+        "doas" => Some("doas"),
+        _ => None,
+    }
+}
+
+/// Copy a local file into the box at /tmp/pkgbridge/<sanitized-basename>: an in-container `cp`
+/// from the bind-mounted host path when possible, otherwise streamed over stdin in fixed-size chunks.
 /// Returns the destination path inside the container.
 pub fn copy_into_box(name: &str, local_path: &std::path::Path) -> Result<String> {
-    let data = std::fs::read(local_path).with_context(|| format!("reading {}", local_path.display()))?;
     let base = local_path
         .file_name()
         .and_then(|s| s.to_str())
@@ -244,19 +604,22 @@ pub fn copy_into_box(name: &str, local_path: &std::path::Path) -> Result<String>
         if ch.is_ascii_alphanumeric() || ch == '.' || ch == '-' || ch == '_' { sanitized.push(ch); } else { sanitized.push('_'); }
     }
     if sanitized.is_empty() { sanitized.push_str("package"); }
-    let dest = format!("/tmp/pkgbridge/{sanitized}");
+    let dest = format!("{STAGING_DIR}/{sanitized}");
+    if !dryrun::perform(format_args!("copy {} into '{}' at {}", local_path.display(), name, dest)) { return Ok(dest); }
     let quoted = shell_escape::escape(std::borrow::Cow::from(dest.clone()));
     let hostp = shell_escape::escape(std::borrow::Cow::from(local_path.to_string_lossy().to_string()));
 
     // First, try an in-container cp from the host path (home is usually bind-mounted by distrobox)
-    let cp_cmd = format!("set -e; mkdir -p /tmp/pkgbridge; if [ -r {host} ]; then cp -f {host} {dst}; exit 0; else exit 1; fi", host=hostp, dst=quoted);
+    let cp_cmd = format!("set -e; mkdir -p {STAGING_DIR}; if [ -r {host} ]; then cp -f {host} {dst}; exit 0; else exit 1; fi", host=hostp, dst=quoted);
     let mut try_cp = Command::new("distrobox");
     try_cp.arg("enter").arg("--no-tty").arg("-n").arg(name).args(["--", "sh", "-lc", &cp_cmd]);
     let cp_status = try_cp.status().with_context(|| format!("attempting in-container cp into {name}"))?;
     if cp_status.success() { return Ok(dest); }
 
     // Fallback: stream bytes via stdin and cat > dest (no TTY)
-    let cmd = format!("set -e; mkdir -p /tmp/pkgbridge && cat > {quoted}");
+    let mut file = std::fs::File::open(local_path).with_context(|| format!("reading {}", local_path.display()))?;
+    let total = file.metadata().map(|m| m.len()).unwrap_or(0);
+    let cmd = format!("set -e; mkdir -p {STAGING_DIR} && cat > {quoted}");
     let mut child = Command::new("distrobox")
         .arg("enter")
         .arg("--no-tty")
@@ -265,11 +628,30 @@ pub fn copy_into_box(name: &str, local_path: &std::path::Path) -> Result<String>
         .stdin(Stdio::piped())
         .spawn()
         .with_context(|| format!("spawning distrobox enter for copy into {name}"))?;
-    child
-        .stdin
-        .as_mut()
-        .ok_or_else(|| anyhow!("failed to open stdin to container"))?
-        .write_all(&data)?;
+    let mut stdin = child.stdin.take().ok_or_else(|| anyhow!("failed to open stdin to container"))?;
+    let show_progress = total >= PROGRESS_THRESHOLD && std::io::stderr().is_terminal();
+    let mut buf = vec![0u8; COPY_CHUNK];
+    let mut sent: u64 = 0;
+    let mut last_pct = u64::MAX;
+    loop {
+        let n = file.read(&mut buf).with_context(|| format!("reading {}", local_path.display()))?;
+        if n == 0 { break; }
+        if let Err(e) = stdin.write_all(&buf[..n]) {
+            // The container side went away; its exit status explains why
+            let _ = child.wait();
+            return Err(anyhow!("copy into container failed: {e}"));
+        }
+        sent += n as u64;
+        if show_progress {
+            let pct = sent * 100 / total;
+            if pct != last_pct {
+                eprint!("\rCopying into {name}: {pct:>3}% ({} / {} MiB)", sent >> 20, total >> 20);
+                last_pct = pct;
+            }
+        }
+    }
+    if show_progress { eprintln!(); }
+    drop(stdin);
     let status = child.wait()?;
     if !status.success() {
         return Err(anyhow!("copy into container failed"));
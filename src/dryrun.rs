@@ -0,0 +1,100 @@
+//! `--dry-run` recorder: host writes, container changes and exports go through here and are
+//! printed instead of performed while dry-run is on. Reads and queries never come through here.
+//! Host file changes that do happen are appended to the journal.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::{Command, ExitStatus};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::journal::{self, Change};
+use crate::quote;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Turn dry-run on for the rest of the process.
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Record `action` ("remove X", "run Y in Z") when dry-run is on.
+/// Returns whether the caller should go ahead and perform it.
+pub fn perform(action: impl std::fmt::Display) -> bool {
+    if !enabled() { return true; }
+    println!("--dry-run: would {}", action);
+    false
+}
+
+pub fn write(path: &Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    if !perform(format_args!("write {}", path.display())) { return Ok(()); }
+    let existed = path.exists();
+    fs::write(path, contents)?;
+    journal::record(if existed { Change::Modified } else { Change::Created }, path);
+    Ok(())
+}
+
+pub fn append(path: &Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    use std::io::Write;
+    if !perform(format_args!("append to {}", path.display())) { return Ok(()); }
+    let existed = path.exists();
+    fs::OpenOptions::new().create(true).append(true).open(path)?.write_all(contents.as_ref())?;
+    journal::record(if existed { Change::Modified } else { Change::Created }, path);
+    Ok(())
+}
+
+/// Directories that already exist are not reported.
+pub fn create_dir_all(path: &Path) -> io::Result<()> {
+    if path.is_dir() || !perform(format_args!("create directory {}", path.display())) { return Ok(()); }
+    fs::create_dir_all(path)?;
+    journal::record(Change::Created, path);
+    Ok(())
+}
+
+pub fn remove_file(path: &Path) -> io::Result<()> {
+    if !perform(format_args!("remove {}", path.display())) { return Ok(()); }
+    fs::remove_file(path)?;
+    journal::record(Change::Deleted, path);
+    Ok(())
+}
+
+pub fn remove_dir_all(path: &Path) -> io::Result<()> {
+    if !perform(format_args!("remove {} and its contents", path.display())) { return Ok(()); }
+    fs::remove_dir_all(path)?;
+    journal::record(Change::Deleted, path);
+    Ok(())
+}
+
+pub fn copy(from: &Path, to: &Path) -> io::Result<()> {
+    if !perform(format_args!("copy {} to {}", from.display(), to.display())) { return Ok(()); }
+    let existed = to.exists();
+    fs::copy(from, to)?;
+    journal::record(if existed { Change::Modified } else { Change::Created }, to);
+    Ok(())
+}
+
+/// Not journaled: modes are only set on files that were just written, which already are.
+pub fn set_permissions(path: &Path, perms: fs::Permissions) -> io::Result<()> {
+    if !perform(format_args!("change permissions of {}", path.display())) { return Ok(()); }
+    fs::set_permissions(path, perms)
+}
+
+/// Run a host command that changes something (`distrobox-export`, `xdg-mime`, ...).
+/// Under dry-run it is printed and reported as successful.
+pub fn status(cmd: &mut Command) -> io::Result<ExitStatus> {
+    if !perform(format_args!("run {}", describe(cmd))) { return Ok(ExitStatus::default()); }
+    cmd.status()
+}
+
+/// `cmd` as a copy-pasteable shell line.
+pub fn describe(cmd: &Command) -> String {
+    std::iter::once(cmd.get_program())
+        .chain(cmd.get_args())
+        .map(|a| quote::sh(&a.to_string_lossy()))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
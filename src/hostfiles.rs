@@ -0,0 +1,172 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// How a host-side launcher or desktop entry was produced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    /// Wrapper written by `distrobox-export --bin`
+    DistroboxExport,
+    /// `.desktop` file exported by `distrobox-export --app` or rewritten by pkgbridge on collision
+    DesktopEntry,
+    /// Fallback `exec distrobox enter` shim written by pkgbridge
+    Shim,
+    /// Package-manager shim from `pm generate-shims`
+    PmShim,
+}
+
+impl Kind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Kind::DistroboxExport => "distrobox-export wrapper",
+            Kind::DesktopEntry => "desktop entry",
+            Kind::Shim => "shim",
+            Kind::PmShim => "package-manager shim",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HostEntry {
+    pub path: PathBuf,
+    pub box_name: String,
+    pub kind: Kind,
+    /// Command run inside the box, when it can be read from the launcher
+    pub target: Option<String>,
+    /// Family key baked into package-manager shims
+    pub family: Option<String>,
+}
+
+/// Inspect a host file and return the box it launches into, if any.
+pub fn inspect(path: &Path) -> Option<HostEntry> {
+    let meta = fs::metadata(path).ok()?;
+    // Launchers are small text files; skip anything else without reading it
+    if !meta.is_file() || meta.len() > 256 * 1024 { return None; }
+    let content = fs::read_to_string(path).ok()?;
+    let is_desktop = path.extension().and_then(|e| e.to_str()) == Some("desktop");
+    let (box_name, kind) = if is_desktop {
+        (enter_target(&content)?, Kind::DesktopEntry)
+    } else if let Some(b) = stamp_field(&content, "box").or_else(|| assigned(&content, "box=")) {
+        (b, Kind::PmShim)
+    } else if content.contains("# distrobox_binary") {
+        let b = content.lines().find_map(|l| l.trim().strip_prefix("# name:")).map(|s| s.trim().to_string())
+            .or_else(|| enter_target(&content))?;
+        (b, Kind::DistroboxExport)
+    } else {
+        (enter_target(&content)?, Kind::Shim)
+    };
+    let target = if is_desktop {
+        content.lines().find_map(|l| l.strip_prefix("Exec=")).and_then(command_after_separator)
+    } else {
+        content.lines().filter(|l| l.contains("distrobox")).find_map(command_after_separator)
+    };
+    let family = stamp_field(&content, "family").or_else(|| assigned(&content, "fam="));
+    Some(HostEntry { path: path.to_path_buf(), box_name, kind, target, family })
+}
+
+/// All launchers and desktop entries in `dir` that run inside `box_name`.
+pub fn bound_to_box(dir: &Path, box_name: &str) -> Vec<HostEntry> {
+    let Ok(rd) = fs::read_dir(dir) else { return vec![]; };
+    let mut out: Vec<HostEntry> = rd
+        .filter_map(|e| e.ok())
+        .filter_map(|e| inspect(&e.path()))
+        .filter(|h| h.box_name == box_name)
+        .collect();
+    out.sort_by(|a, b| a.path.cmp(&b.path));
+    out
+}
+
+/// Value from the `# pkgbridge-shim-<key>: value` header of package-manager shims.
+fn stamp_field(content: &str, key: &str) -> Option<String> {
+    let prefix = format!("# pkgbridge-shim-{}: ", key);
+    content.lines().take(8).find_map(|l| l.strip_prefix(prefix.as_str())).map(|v| v.trim().to_string())
+}
+
+/// Value of a `key="value"` shell assignment at the start of a line.
+fn assigned(content: &str, key: &str) -> Option<String> {
+    content.lines().find_map(|l| l.trim().strip_prefix(key)).map(unquote).filter(|v| !v.is_empty() && !v.starts_with('$'))
+}
+
+/// Box name passed to `distrobox enter -n`/`distrobox-enter --name` anywhere in the content.
+fn enter_target(content: &str) -> Option<String> {
+    for line in content.lines() {
+        if !line.contains("distrobox") { continue; }
+        let toks: Vec<&str> = line.split_whitespace().collect();
+        for w in toks.windows(2) {
+            if w[0] == "-n" || w[0] == "--name" {
+                let v = unquote(w[1]);
+                if !v.is_empty() && !v.starts_with('$') { return Some(v); }
+            }
+        }
+    }
+    None
+}
+
+/// First word after `--` in a `distrobox enter ... -- cmd` line, skipping elevation helpers.
+fn command_after_separator(line: &str) -> Option<String> {
+    let toks: Vec<&str> = line.split_whitespace().collect();
+    let pos = toks.iter().position(|t| *t == "--")?;
+    toks[pos + 1..]
+        .iter()
+        .map(|t| unquote(t))
+        .find(|t| !t.is_empty() && t != "sudo" && t != "doas" && !t.starts_with('$') && !t.starts_with('%'))
+}
+
+/// Undo sh word quoting (`'...'`, `"..."`, backslashes) as written by pkgbridge's templates.
+fn unquote(s: &str) -> String {
+    let mut out = String::new();
+    let mut chars = s.trim().chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => out.extend(chars.by_ref().take_while(|&c| c != '\'')),
+            '"' => {
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => out.extend(chars.next()),
+                        _ => out.push(c),
+                    }
+                }
+            }
+            '\\' => out.extend(chars.next()),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unquote_handles_each_quoting_style() {
+        assert_eq!(unquote("plain"), "plain");
+        assert_eq!(unquote("'single quoted'"), "single quoted");
+        assert_eq!(unquote(r#""double \"quoted\"""#), r#"double "quoted""#);
+        assert_eq!(unquote(r"back\ slash"), "back slash");
+        assert_eq!(unquote(r#"mix'ed '"quo"tes"#), "mixed quotes");
+        assert_eq!(unquote("  -rf  "), "-rf");
+    }
+
+    #[test]
+    fn unquote_keeps_shell_syntax_literal() {
+        assert_eq!(unquote("'$(id)'"), "$(id)");
+        assert_eq!(unquote("'`id`'"), "`id`");
+        assert_eq!(unquote(r#""\$HOME""#), "$HOME");
+        assert_eq!(unquote("'100%'"), "100%");
+    }
+
+    #[test]
+    fn unquote_reverses_quote_sh() {
+        for s in ["", "it's", "'", "say \"hi\"", "$(touch /tmp/x)", "`id`", "100%", "two words", "line\nbreak", "-rf", r"back\slash"] {
+            assert_eq!(unquote(&crate::quote::sh(s)), s, "quoted as {}", crate::quote::sh(s));
+        }
+    }
+
+    #[test]
+    fn unquote_tolerates_unterminated_quotes() {
+        assert_eq!(unquote("'open"), "open");
+        assert_eq!(unquote(r#""open"#), "open");
+        assert_eq!(unquote("trailing\\"), "trailing");
+    }
+}
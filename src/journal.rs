@@ -0,0 +1,62 @@
+//! Append-only journal of the host paths pkgbridge creates, modifies or deletes.
+//! One tab-separated line per change: RFC 3339 time, change, path, originating command.
+
+use std::io::Write;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use crate::config;
+use crate::dryrun;
+use crate::quote;
+
+static COMMAND: OnceLock<String> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy)]
+pub enum Change {
+    Created,
+    Modified,
+    Deleted,
+}
+
+impl Change {
+    pub fn label(self) -> &'static str {
+        match self {
+            Change::Created => "created",
+            Change::Modified => "modified",
+            Change::Deleted => "deleted",
+        }
+    }
+}
+
+/// Remember the command line later records are attributed to.
+pub fn set_command(args: impl IntoIterator<Item = String>) {
+    let line = args.into_iter().map(|a| quote::sh(&a)).collect::<Vec<_>>().join(" ");
+    let _ = COMMAND.set(line);
+}
+
+/// Append a record for `path`. Nothing is recorded under dry-run, and paths in the state
+/// directory (state, snapshots, archive, the journal itself) are pkgbridge's own bookkeeping.
+pub fn record(change: Change, path: &Path) {
+    if dryrun::enabled() || path.starts_with(config::state_dir()) { return; }
+    let journal = config::journal_path();
+    if let Some(dir) = journal.parent() { let _ = std::fs::create_dir_all(dir); }
+    let line = format!(
+        "{}\t{}\t{}\t{}\n",
+        humantime::format_rfc3339_seconds(std::time::SystemTime::now()),
+        change.label(),
+        path.display(),
+        COMMAND.get().map(String::as_str).unwrap_or("pkgbridge"),
+    );
+    let res = std::fs::OpenOptions::new().create(true).append(true).open(&journal).and_then(|mut f| f.write_all(line.as_bytes()));
+    if let Err(e) = res { log::warn!("could not append to {}: {}", journal.display(), e); }
+}
+
+/// Record what an external tool did to `path`, given whether it existed before the tool ran.
+pub fn record_outcome(path: &Path, existed: bool) {
+    match (existed, path.exists()) {
+        (false, true) => record(Change::Created, path),
+        (true, true) => record(Change::Modified, path),
+        (true, false) => record(Change::Deleted, path),
+        (false, false) => {}
+    }
+}
@@ -1,9 +1,19 @@
 mod cli;
 mod pkgdetect;
+mod pkgmeta;
 mod distro;
 mod pm;
 mod config;
 mod desktop;
+mod hostfiles;
+mod pkgops;
+mod provision;
+mod verify;
+mod archive;
+mod quote;
+mod repos;
+mod dryrun;
+mod journal;
 
 use anyhow::Result;
 
@@ -21,9 +21,9 @@ pub fn detect_package_format(path: &Path) -> Result<PackageFormat> {
     let n = f.read(&mut header)?;
     if n >= 8 {
         // RPM lead magic: 0xed 0xab 0xee 0xdb
-        if &header[0..4] == [0xed, 0xab, 0xee, 0xdb] { return Ok(PackageFormat::Rpm); }
+        if header[0..4] == [0xed, 0xab, 0xee, 0xdb] { return Ok(PackageFormat::Rpm); }
         // ar archive magic for .deb: "!<arch>\n"
-        if &header[0..8] == *b"!<arch>\n" {
+        if header[0..8] == *b"!<arch>\n" {
             // further check for debian-binary member (best-effort quick scan)
             // Not reading whole ar; just a hint that this is likely a .deb
             return Ok(PackageFormat::Deb);
@@ -0,0 +1,424 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+
+use crate::pkgdetect::PackageFormat;
+
+/// Metadata read from a package file on the host, without entering any box.
+#[derive(Debug, Clone, Default)]
+pub struct PackageMeta {
+    pub name: String,
+    pub version: String,
+    pub arch: String,
+    pub maintainer: String,
+    pub summary: String,
+    pub depends: Vec<String>,
+    pub scripts: Vec<Scriptlet>,
+    /// Absolute paths shipped by the package
+    pub files: Vec<String>,
+}
+
+/// A maintainer script (deb) or scriptlet (rpm) run by the package manager as root.
+#[derive(Debug, Clone)]
+pub struct Scriptlet {
+    pub name: String,
+    pub body: String,
+    /// Interpreter rpm runs the body with (`-p`); may run on its own with an empty body. Empty for deb.
+    pub interpreter: String,
+}
+
+impl Scriptlet {
+    /// Name plus a non-default interpreter, e.g. `%post (-p /sbin/ldconfig)`.
+    pub fn heading(&self) -> String {
+        match self.interpreter.as_str() {
+            "" | "/bin/sh" => self.name.clone(),
+            prog => format!("{} (-p {})", self.name, prog),
+        }
+    }
+}
+
+pub fn read_package(path: &Path, fmt: PackageFormat) -> Result<PackageMeta> {
+    match fmt {
+        PackageFormat::Deb => read_deb(path),
+        PackageFormat::Rpm => read_rpm(path),
+    }
+    .with_context(|| format!("reading package metadata from {}", path.display()))
+}
+
+/// Bins under /usr/bin and desktop entries under /usr/share/applications, as the exporter sees them.
+pub fn exportable(files: &[String]) -> (Vec<String>, Vec<String>) {
+    let mut bins = Vec::new();
+    let mut apps = Vec::new();
+    for f in files {
+        let path = f.trim_start_matches('.').trim_start_matches('/');
+        if let Some(name) = path.strip_prefix("usr/bin/") {
+            if !name.is_empty() && !name.ends_with('/') { bins.push(name.to_string()); }
+        }
+        if let Some(rest) = path.strip_prefix("usr/share/applications/") {
+            if rest.ends_with(".desktop") { apps.push(rest.to_string()); }
+        }
+    }
+    bins.sort(); bins.dedup();
+    apps.sort(); apps.dedup();
+    (bins, apps)
+}
+
+/// Scripts that execute code at install/remove time (deb `triggers` only declares interest).
+/// An rpm scriptlet with only an interpreter (`%post -p /sbin/ldconfig`) still runs it.
+pub fn executable_scripts(meta: &PackageMeta) -> Vec<&Scriptlet> {
+    meta.scripts
+        .iter()
+        .filter(|s| s.name != "triggers" && !(s.body.trim().is_empty() && s.interpreter.trim().is_empty()))
+        .collect()
+}
+
+/// Notable actions spotted in script bodies, as short human-readable phrases.
+pub fn script_findings(scripts: &[&Scriptlet]) -> Vec<&'static str> {
+    const RULES: &[(&[&str], &str)] = &[
+        (&["sources.list", "add-apt-repository", "apt-key", "/etc/apt/keyrings", "trusted.gpg"], "adds apt sources or signing keys"),
+        (&["yum.repos.d", "zypp/repos.d", "config-manager", "rpm --import", "rpmkeys --import", "zypper ar", "zypper addrepo"], "adds package repositories or signing keys"),
+        (&["systemctl", "service ", "invoke-rc.d", "update-rc.d", "deb-systemd-helper"], "enables, starts or restarts services"),
+        (&["useradd", "adduser", "groupadd", "addgroup", "usermod"], "creates or modifies users/groups"),
+        (&["/etc/"], "writes to /etc"),
+        (&["curl ", "wget ", "http://", "https://"], "downloads from or references the network"),
+        (&["setcap", "chmod u+s", "chmod 4", "chmod +s"], "sets setuid bits or file capabilities"),
+        (&["crontab", "/etc/cron"], "installs cron jobs"),
+        (&["modprobe", "dkms", "insmod"], "builds or loads kernel modules"),
+        (&["rm -rf", "rm -fr"], "recursively deletes files"),
+    ];
+    let mut found = Vec::new();
+    for (needles, what) in RULES {
+        if scripts.iter().any(|s| needles.iter().any(|n| s.body.contains(n))) {
+            found.push(*what);
+        }
+    }
+    found
+}
+
+// ---- deb: ar archive with control.tar.* and data.tar.* members ----
+
+/// One member of an `ar` archive.
+pub struct ArMember {
+    pub name: String,
+    pub offset: u64,
+    pub size: u64,
+}
+
+pub fn ar_members(f: &mut File) -> Result<Vec<ArMember>> {
+    f.seek(SeekFrom::Start(0))?;
+    let mut magic = [0u8; 8];
+    f.read_exact(&mut magic)?;
+    if &magic != b"!<arch>\n" { return Err(anyhow!("not an ar archive")); }
+    let mut members = Vec::new();
+    let mut pos = 8u64;
+    loop {
+        let mut hdr = [0u8; 60];
+        match f.read_exact(&mut hdr) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.into()),
+        }
+        let name = String::from_utf8_lossy(&hdr[0..16]).trim().trim_end_matches('/').to_string();
+        let size: u64 = String::from_utf8_lossy(&hdr[48..58]).trim().parse().map_err(|_| anyhow!("bad ar member size"))?;
+        let offset = pos + 60;
+        members.push(ArMember { name, offset, size });
+        // Members are padded to an even offset
+        pos = offset + size + (size % 2);
+        f.seek(SeekFrom::Start(pos))?;
+    }
+    Ok(members)
+}
+
+/// Decompressing reader for a `*.tar[.gz|.xz|.zst]` member, chosen by its name.
+fn member_reader<'a>(f: &'a mut File, m: &ArMember) -> Result<Box<dyn Read + 'a>> {
+    f.seek(SeekFrom::Start(m.offset))?;
+    let raw = BufReader::new(f.take(m.size));
+    Ok(if m.name.ends_with(".gz") {
+        Box::new(flate2::read::GzDecoder::new(raw))
+    } else if m.name.ends_with(".xz") {
+        Box::new(xz2::read::XzDecoder::new(raw))
+    } else if m.name.ends_with(".zst") {
+        Box::new(zstd::stream::read::Decoder::new(raw)?)
+    } else if m.name.ends_with(".tar") {
+        Box::new(raw)
+    } else {
+        return Err(anyhow!("unsupported compression for {}", m.name));
+    })
+}
+
+const DEB_SCRIPTS: &[&str] = &["preinst", "postinst", "prerm", "postrm", "config", "triggers"];
+
+fn read_deb(path: &Path) -> Result<PackageMeta> {
+    let mut f = File::open(path)?;
+    let members = ar_members(&mut f)?;
+    let control = members.iter().find(|m| m.name.starts_with("control.tar")).ok_or_else(|| anyhow!("control archive missing"))?;
+    let data = members.iter().find(|m| m.name.starts_with("data.tar")).ok_or_else(|| anyhow!("data archive missing"))?;
+
+    let mut meta = PackageMeta::default();
+    let mut control_text = String::new();
+    {
+        let mut ar = tar::Archive::new(member_reader(&mut f, control)?);
+        for entry in ar.entries()? {
+            let mut entry = entry?;
+            let p = entry.path()?.to_string_lossy().trim_start_matches("./").to_string();
+            if p == "control" {
+                entry.read_to_string(&mut control_text)?;
+            } else if DEB_SCRIPTS.contains(&p.as_str()) {
+                let mut body = String::new();
+                entry.read_to_string(&mut body).ok();
+                meta.scripts.push(Scriptlet { name: p, body, interpreter: String::new() });
+            }
+        }
+    }
+    let fields = parse_control(&control_text);
+    let get = |k: &str| fields.get(k).cloned().unwrap_or_default();
+    meta.name = get("package");
+    meta.version = get("version");
+    meta.arch = get("architecture");
+    meta.maintainer = get("maintainer");
+    meta.summary = get("description").lines().next().unwrap_or("").to_string();
+    for k in ["pre-depends", "depends"] {
+        meta.depends.extend(get(k).split(',').map(|d| d.trim().to_string()).filter(|d| !d.is_empty()));
+    }
+    meta.scripts.sort_by_key(|s| DEB_SCRIPTS.iter().position(|n| *n == s.name));
+
+    let mut ar = tar::Archive::new(member_reader(&mut f, data)?);
+    for entry in ar.entries()? {
+        let entry = entry?;
+        if entry.header().entry_type().is_dir() { continue; }
+        let p = entry.path()?.to_string_lossy().trim_start_matches('.').to_string();
+        meta.files.push(if p.starts_with('/') { p } else { format!("/{}", p) });
+    }
+    Ok(meta)
+}
+
+/// Parse an RFC822-style control file into lowercase field names; continuation lines are kept.
+fn parse_control(s: &str) -> HashMap<String, String> {
+    let mut out: HashMap<String, String> = HashMap::new();
+    let mut last: Option<String> = None;
+    for line in s.lines() {
+        if line.starts_with(' ') || line.starts_with('\t') {
+            if let Some(k) = &last {
+                if let Some(v) = out.get_mut(k) { v.push('\n'); v.push_str(line.trim()); }
+            }
+            continue;
+        }
+        if let Some((k, v)) = line.split_once(':') {
+            let k = k.trim().to_ascii_lowercase();
+            out.insert(k.clone(), v.trim().to_string());
+            last = Some(k);
+        }
+    }
+    out
+}
+
+// ---- rpm: lead, signature header, main header, payload ----
+
+pub const RPMTAG_NAME: u32 = 1000;
+pub const RPMTAG_VERSION: u32 = 1001;
+pub const RPMTAG_RELEASE: u32 = 1002;
+pub const RPMTAG_SUMMARY: u32 = 1004;
+pub const RPMTAG_VENDOR: u32 = 1011;
+pub const RPMTAG_PACKAGER: u32 = 1015;
+pub const RPMTAG_ARCH: u32 = 1022;
+pub const RPMTAG_OLDFILENAMES: u32 = 1027;
+pub const RPMTAG_REQUIRENAME: u32 = 1049;
+pub const RPMTAG_REQUIREVERSION: u32 = 1050;
+pub const RPMTAG_DIRINDEXES: u32 = 1116;
+pub const RPMTAG_BASENAMES: u32 = 1117;
+pub const RPMTAG_DIRNAMES: u32 = 1118;
+pub const RPMTAG_PAYLOADDIGEST: u32 = 5092;
+pub const RPMTAG_PAYLOADDIGESTALGO: u32 = 5093;
+
+/// Scriptlet body and interpreter (`*PROG`) tags, and the names rpm prints for them.
+const RPM_SCRIPTS: &[(u32, u32, &str)] = &[
+    (1151, 1153, "%pretrans"),
+    (1023, 1085, "%pre"),
+    (1024, 1086, "%post"),
+    (1025, 1087, "%preun"),
+    (1026, 1088, "%postun"),
+    (1152, 1154, "%posttrans"),
+];
+
+/// Trigger tags: script bodies and interpreters (one per script), then the condition names,
+/// the script each condition runs and the condition flags (one per condition).
+const RPM_TRIGGERS: &[(u32, u32, u32, u32, u32, &str)] = &[
+    (1065, 1092, 1066, 1069, 1068, "%trigger"),
+    (5066, 5067, 5069, 5070, 5072, "%filetrigger"),
+    (5076, 5077, 5079, 5080, 5082, "%transfiletrigger"),
+];
+
+/// A parsed header structure (signature or main header).
+pub struct RpmHeader {
+    entries: Vec<(u32, u32, u32, u32)>, // tag, type, offset, count
+    store: Vec<u8>,
+    /// The header exactly as stored in the file (magic through data store); header signatures cover this
+    pub raw: Vec<u8>,
+}
+
+impl RpmHeader {
+    fn entry(&self, tag: u32) -> Option<(u32, u32, u32)> {
+        self.entries.iter().find(|e| e.0 == tag).map(|e| (e.1, e.2, e.3))
+    }
+
+    /// Raw bytes of a BIN tag.
+    pub fn bin(&self, tag: u32) -> Option<Vec<u8>> {
+        let (ty, off, count) = self.entry(tag)?;
+        if ty != 7 { return None; }
+        self.store.get(off as usize..off as usize + count as usize).map(|b| b.to_vec())
+    }
+
+    /// First string of a STRING, STRING_ARRAY or I18NSTRING tag.
+    pub fn string(&self, tag: u32) -> Option<String> {
+        self.strings(tag).into_iter().next()
+    }
+
+    pub fn strings(&self, tag: u32) -> Vec<String> {
+        let Some((ty, off, count)) = self.entry(tag) else { return vec![]; };
+        if !matches!(ty, 6 | 8 | 9) { return vec![]; }
+        let n = if ty == 6 { 1 } else { count as usize };
+        // `count` comes from the file; the data store bounds what can really be there
+        let mut out = Vec::new();
+        let mut pos = off as usize;
+        for _ in 0..n {
+            let Some(rest) = self.store.get(pos..).filter(|r| !r.is_empty()) else { break; };
+            let end = rest.iter().position(|&b| b == 0).unwrap_or(rest.len());
+            out.push(String::from_utf8_lossy(&rest[..end]).to_string());
+            pos += end + 1;
+        }
+        out
+    }
+
+    pub fn int32s(&self, tag: u32) -> Vec<u32> {
+        let Some((ty, off, count)) = self.entry(tag) else { return vec![]; };
+        if ty != 4 { return vec![]; }
+        self.store
+            .get(off as usize..)
+            .unwrap_or_default()
+            .chunks_exact(4)
+            .take(count as usize)
+            .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+            .collect()
+    }
+}
+
+fn read_u32(b: &[u8]) -> u32 { u32::from_be_bytes([b[0], b[1], b[2], b[3]]) }
+
+fn read_rpm_header(f: &mut File) -> Result<RpmHeader> {
+    let mut intro = [0u8; 16];
+    f.read_exact(&mut intro)?;
+    if intro[0..3] != [0x8e, 0xad, 0xe8] { return Err(anyhow!("bad rpm header magic")); }
+    let nindex = read_u32(&intro[8..12]) as usize;
+    let hsize = read_u32(&intro[12..16]) as usize;
+    if nindex > 1 << 16 || hsize > 1 << 28 { return Err(anyhow!("rpm header too large")); }
+    let mut index = vec![0u8; nindex * 16];
+    f.read_exact(&mut index)?;
+    let mut store = vec![0u8; hsize];
+    f.read_exact(&mut store)?;
+    let entries = index
+        .chunks(16)
+        .map(|e| (read_u32(&e[0..4]), read_u32(&e[4..8]), read_u32(&e[8..12]), read_u32(&e[12..16])))
+        .collect();
+    let mut raw = Vec::with_capacity(16 + index.len() + store.len());
+    raw.extend_from_slice(&intro);
+    raw.extend_from_slice(&index);
+    raw.extend_from_slice(&store);
+    Ok(RpmHeader { entries, store, raw })
+}
+
+/// Read the signature header and the main header of an rpm file.
+pub fn read_rpm_headers(path: &Path) -> Result<(RpmHeader, RpmHeader)> {
+    let mut f = File::open(path)?;
+    let mut lead = [0u8; 96];
+    f.read_exact(&mut lead)?;
+    if lead[0..4] != [0xed, 0xab, 0xee, 0xdb] { return Err(anyhow!("bad rpm lead magic")); }
+    let sig = read_rpm_header(&mut f)?;
+    f.seek(SeekFrom::Current(sig_padding(&sig) as i64))?;
+    let main = read_rpm_header(&mut f)?;
+    Ok((sig, main))
+}
+
+/// The signature header is padded to an 8-byte boundary.
+fn sig_padding(sig: &RpmHeader) -> usize {
+    (8 - (sig.store.len() % 8)) % 8
+}
+
+/// File offset of the (compressed) payload: lead, padded signature header, main header.
+pub fn rpm_payload_offset(sig: &RpmHeader, main: &RpmHeader) -> u64 {
+    (96 + sig.raw.len() + sig_padding(sig) + main.raw.len()) as u64
+}
+
+fn read_rpm(path: &Path) -> Result<PackageMeta> {
+    let (_, h) = read_rpm_headers(path)?;
+    let mut meta = PackageMeta {
+        name: h.string(RPMTAG_NAME).unwrap_or_default(),
+        version: format!("{}-{}", h.string(RPMTAG_VERSION).unwrap_or_default(), h.string(RPMTAG_RELEASE).unwrap_or_default()),
+        arch: h.string(RPMTAG_ARCH).unwrap_or_default(),
+        maintainer: h.string(RPMTAG_PACKAGER).or_else(|| h.string(RPMTAG_VENDOR)).unwrap_or_default(),
+        summary: h.string(RPMTAG_SUMMARY).unwrap_or_default(),
+        ..Default::default()
+    };
+    let names = h.strings(RPMTAG_REQUIRENAME);
+    let versions = h.strings(RPMTAG_REQUIREVERSION);
+    for (i, n) in names.iter().enumerate() {
+        // rpmlib() capabilities are requirements on rpm itself, not packages
+        if n.starts_with("rpmlib(") { continue; }
+        let dep = match versions.get(i).filter(|v| !v.is_empty()) {
+            Some(v) => format!("{} {}", n, v),
+            None => n.clone(),
+        };
+        if !meta.depends.contains(&dep) { meta.depends.push(dep); }
+    }
+    for &(tag, prog_tag, name) in RPM_SCRIPTS {
+        let body = h.string(tag);
+        // A multi-word `-p` interpreter is stored as an array
+        let interpreter = h.strings(prog_tag).join(" ");
+        if body.is_some() || !interpreter.is_empty() {
+            meta.scripts.push(Scriptlet { name: name.to_string(), body: body.unwrap_or_default(), interpreter });
+        }
+    }
+    rpm_triggers(&h, &mut meta.scripts);
+    let basenames = h.strings(RPMTAG_BASENAMES);
+    if basenames.is_empty() {
+        meta.files = h.strings(RPMTAG_OLDFILENAMES);
+    } else {
+        let dirs = h.strings(RPMTAG_DIRNAMES);
+        let idx = h.int32s(RPMTAG_DIRINDEXES);
+        for (i, b) in basenames.iter().enumerate() {
+            let d = idx.get(i).and_then(|&j| dirs.get(j as usize)).map(|s| s.as_str()).unwrap_or("/");
+            meta.files.push(format!("{}{}", d, b));
+        }
+    }
+    Ok(meta)
+}
+
+/// Trigger scriptlets, named like rpm's spec syntax (`%triggerin -- glibc`).
+fn rpm_triggers(h: &RpmHeader, out: &mut Vec<Scriptlet>) {
+    for &(scripts_tag, prog_tag, name_tag, index_tag, flags_tag, prefix) in RPM_TRIGGERS {
+        let bodies = h.strings(scripts_tag);
+        let progs = h.strings(prog_tag);
+        let names = h.strings(name_tag);
+        let index = h.int32s(index_tag);
+        let flags = h.int32s(flags_tag);
+        for i in 0..bodies.len().max(progs.len()) {
+            let conds: Vec<usize> = (0..index.len()).filter(|&j| index[j] as usize == i).collect();
+            // RPMSENSE_TRIGGERIN/UN/POSTUN/PREIN
+            let kind = match conds.first().and_then(|&j| flags.get(j)).copied().unwrap_or(0) {
+                f if f & (1 << 25) != 0 => "prein",
+                f if f & (1 << 18) != 0 => "postun",
+                f if f & (1 << 17) != 0 => "un",
+                _ => "in",
+            };
+            let on: Vec<&str> = conds.iter().filter_map(|&j| names.get(j)).map(String::as_str).collect();
+            let name = if on.is_empty() { format!("{}{}", prefix, kind) } else { format!("{}{} -- {}", prefix, kind, on.join(", ")) };
+            out.push(Scriptlet {
+                name,
+                body: bodies.get(i).cloned().unwrap_or_default(),
+                interpreter: progs.get(i).cloned().unwrap_or_default(),
+            });
+        }
+    }
+}
@@ -0,0 +1,402 @@
+use crate::distro::Family;
+
+fn quote_all(names: &[String]) -> String {
+    names
+        .iter()
+        .map(|n| shell_escape::escape(std::borrow::Cow::from(n.clone())).to_string())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Package-manager binaries allowed without a password by the `box nopasswd` sudoers drop-in.
+pub fn pm_binaries(fam: Family) -> &'static [&'static str] {
+    match fam {
+        Family::Debian => &["/usr/bin/apt", "/usr/bin/apt-get", "/usr/bin/dpkg"],
+        Family::Fedora => &["/usr/bin/dnf", "/usr/bin/dnf5", "/usr/bin/yum", "/usr/bin/rpm"],
+        Family::OpenSuse => &["/usr/bin/zypper", "/usr/bin/rpm"],
+        Family::Arch => &["/usr/bin/pacman"],
+        Family::Alpine => &["/sbin/apk"],
+        Family::Void => &["/usr/bin/xbps-install", "/usr/bin/xbps-remove"],
+        Family::Gentoo => &["/usr/bin/emerge"],
+    }
+}
+
+/// Root command that refreshes indexes and installs packages by name from the box's repositories.
+pub fn install_names_cmd(fam: Family, names: &[String]) -> String {
+    let p = quote_all(names);
+    match fam {
+        Family::Debian => format!("set -e; export DEBIAN_FRONTEND=noninteractive; apt-get -y update; apt-get -y install {}", p),
+        Family::Fedora => format!("set -e; if command -v dnf >/dev/null; then dnf -y install {}; else yum -y install {}; fi", p, p),
+        Family::OpenSuse => format!("set -e; zypper --non-interactive refresh; zypper --non-interactive install {}", p),
+        Family::Arch => format!("set -e; pacman -Sy --noconfirm --needed {}", p),
+        Family::Alpine => format!("set -e; apk update; apk add {}", p),
+        Family::Void => format!("set -e; xbps-install -Sy {}", p),
+        Family::Gentoo => format!("set -e; emerge --noreplace {}", p),
+    }
+}
+
+/// Command listing installed packages as `name<TAB>version` lines (pacman separates with a space).
+pub fn list_installed_cmd(fam: Family) -> String {
+    match fam {
+        Family::Debian => "dpkg-query -W -f='${Package}\\t${Version}\\n'".to_string(),
+        Family::Fedora | Family::OpenSuse => "rpm -qa --qf '%{NAME}\\t%{VERSION}-%{RELEASE}\\n'".to_string(),
+        Family::Arch => "pacman -Q".to_string(),
+        // These print `name-version` in one word; split it where the version starts
+        Family::Alpine => format!("apk info -v 2>/dev/null | {}", split_pkgver_awk("-[^-]+-r[0-9]+$")),
+        Family::Void => format!("xbps-query -l | awk '{{ print $2 }}' | {}", split_pkgver_awk("-[^-]+$")),
+        Family::Gentoo => format!("cd /var/db/pkg && for p in */*; do echo \"$p\"; done | {}", split_pkgver_awk("-[0-9][^-]*(-r[0-9]+)?$")),
+    }
+}
+
+/// awk program turning `name-version` words into `name<TAB>version`, where `version_re` matches the version suffix.
+fn split_pkgver_awk(version_re: &str) -> String {
+    format!("awk '{{ n = $0; sub(/{}/, \"\", n); print n \"\\t\" substr($0, length(n) + 2) }}'", version_re)
+}
+
+/// Command listing the files owned by an installed package, one absolute path per line.
+pub fn files_cmd(fam: Family, pkg: &str) -> String {
+    let p = shell_escape::escape(std::borrow::Cow::from(pkg.to_string()));
+    match fam {
+        Family::Debian => format!("dpkg -L {}", p),
+        Family::Fedora | Family::OpenSuse => format!("rpm -ql {}", p),
+        Family::Arch => format!("pacman -Qlq {}", p),
+        Family::Alpine => format!("apk info -L {} 2>/dev/null | awk 'NR > 1 && NF {{ print \"/\" $0 }}'", p),
+        Family::Void => format!("xbps-query -f {} | sed 's/ -> .*//'", p),
+        // CONTENTS lines: `obj <path> <md5> <mtime>`, `sym <path> -> <target> <mtime>`
+        Family::Gentoo => format!(
+            "for d in /var/db/pkg/{p}-[0-9]* /var/db/pkg/*/{p}-[0-9]*; do [ -f \"$d/CONTENTS\" ] && awk '$1 == \"obj\" {{ sub(/^obj /, \"\"); sub(/ [^ ]+ [^ ]+$/, \"\"); print }} $1 == \"sym\" {{ sub(/^sym /, \"\"); sub(/ -> .*$/, \"\"); print }}' \"$d/CONTENTS\"; done; true",
+            p = p
+        ),
+    }
+}
+
+/// Root command that refreshes indexes and upgrades every package in the box.
+pub fn upgrade_cmd(fam: Family) -> String {
+    match fam {
+        Family::Debian => "set -e; export DEBIAN_FRONTEND=noninteractive; apt-get -y update; apt-get -y full-upgrade".to_string(),
+        Family::Fedora => "set -e; if command -v dnf >/dev/null; then dnf -y upgrade --refresh; else yum -y update; fi".to_string(),
+        // Tumbleweed is rolling and wants dist-upgrade; Leap uses plain update
+        Family::OpenSuse => "set -e; zypper --non-interactive refresh; if grep -qi tumbleweed /etc/os-release; then zypper --non-interactive dup; else zypper --non-interactive update; fi".to_string(),
+        Family::Arch => "set -e; pacman -Syu --noconfirm".to_string(),
+        // Not --available: it resets the versioned world entries `hold` pins packages with
+        Family::Alpine => "set -e; apk update; apk upgrade".to_string(),
+        // xbps has to update itself before it can update anything else
+        Family::Void => "set -e; xbps-install -Syu xbps; xbps-install -Syu".to_string(),
+        Family::Gentoo => "set -e; emerge --sync; emerge --update --deep --newuse @world".to_string(),
+    }
+}
+
+/// Root command that drops the package manager's downloaded packages and metadata caches.
+pub fn clean_cache_cmd(fam: Family) -> String {
+    match fam {
+        Family::Debian => "apt-get clean".to_string(),
+        Family::Fedora => "if command -v dnf >/dev/null; then dnf clean all; else yum clean all; fi".to_string(),
+        Family::OpenSuse => "zypper --non-interactive clean --all".to_string(),
+        Family::Arch => "pacman -Scc --noconfirm".to_string(),
+        Family::Alpine => "rm -rf /var/cache/apk/*".to_string(),
+        Family::Void => "xbps-remove -yO".to_string(),
+        Family::Gentoo => "if command -v eclean-dist >/dev/null; then eclean-dist --deep; else rm -rf /var/cache/distfiles/*; fi".to_string(),
+    }
+}
+
+/// One match from a repository search.
+#[derive(Debug, Clone, Default)]
+pub struct SearchHit {
+    pub package: String,
+    pub version: String,
+    pub summary: String,
+}
+
+/// Command searching the box's repository metadata for `term`.
+pub fn search_cmd(fam: Family, term: &str) -> String {
+    let t = shell_escape::escape(std::borrow::Cow::from(term.to_string()));
+    match fam {
+        Family::Debian => format!("apt-cache search --full {}", t),
+        Family::Fedora => format!("dnf -q search {}", t),
+        Family::OpenSuse => format!("zypper --non-interactive --quiet search -s -t package {}", t),
+        Family::Arch => format!("pacman -Ss {}", t),
+        Family::Alpine => format!("apk search -v {}", t),
+        Family::Void => format!("xbps-query -Rs {}", t),
+        Family::Gentoo => format!("emerge --search {}", t),
+    }
+}
+
+/// Parse the output of [`search_cmd`]; fields the manager does not print are left empty.
+pub fn parse_search(fam: Family, out: &str) -> Vec<SearchHit> {
+    let mut hits: Vec<SearchHit> = Vec::new();
+    match fam {
+        Family::Debian => {
+            // Stanzas of "Field: value" separated by blank lines
+            let mut cur = SearchHit::default();
+            for line in out.lines().chain(std::iter::once("")) {
+                if line.trim().is_empty() {
+                    if !cur.package.is_empty() { hits.push(std::mem::take(&mut cur)); }
+                    continue;
+                }
+                if let Some(v) = line.strip_prefix("Package:") { cur.package = v.trim().to_string(); }
+                else if let Some(v) = line.strip_prefix("Version:") { cur.version = v.trim().to_string(); }
+                else if let Some(v) = line.strip_prefix("Description-en:").or_else(|| line.strip_prefix("Description:")) {
+                    if cur.summary.is_empty() { cur.summary = v.trim().to_string(); }
+                }
+            }
+        }
+        Family::Fedora => {
+            // "name.arch : summary" under "Matched fields"/"=== ... ===" headers
+            for line in out.lines() {
+                let Some((left, summary)) = line.split_once(" : ") else { continue; };
+                let left = left.trim();
+                if left.is_empty() || left.starts_with('=') { continue; }
+                let package = left.rsplit_once('.').map(|(n, _)| n).unwrap_or(left);
+                hits.push(SearchHit { package: package.to_string(), version: String::new(), summary: summary.trim().to_string() });
+            }
+        }
+        Family::OpenSuse => {
+            // "S | Name | Type | Version | Arch | Repository" table
+            for line in out.lines() {
+                let cols: Vec<&str> = line.split('|').map(|c| c.trim()).collect();
+                if cols.len() < 4 || cols[1].is_empty() || cols[1] == "Name" { continue; }
+                if cols.get(2).map(|t| *t != "package").unwrap_or(true) { continue; }
+                hits.push(SearchHit { package: cols[1].to_string(), version: cols[3].to_string(), summary: String::new() });
+            }
+        }
+        Family::Arch => {
+            // "repo/name version [installed]" followed by an indented description line
+            for line in out.lines() {
+                if line.starts_with(char::is_whitespace) {
+                    if let Some(last) = hits.last_mut() {
+                        if last.summary.is_empty() { last.summary = line.trim().to_string(); }
+                    }
+                    continue;
+                }
+                let mut parts = line.split_whitespace();
+                let (Some(full), Some(version)) = (parts.next(), parts.next()) else { continue; };
+                let package = full.rsplit_once('/').map(|(_, n)| n).unwrap_or(full);
+                hits.push(SearchHit { package: package.to_string(), version: version.to_string(), summary: String::new() });
+            }
+        }
+        Family::Alpine => {
+            // "name-1.2.3-r0 - summary"
+            for line in out.lines() {
+                let (pkgver, summary) = line.split_once(" - ").unwrap_or((line, ""));
+                let Some((package, version)) = split_pkgver(pkgver.trim(), 2) else { continue; };
+                hits.push(SearchHit { package, version, summary: summary.trim().to_string() });
+            }
+        }
+        Family::Void => {
+            // "[-] name-1.2.3_1   summary"
+            for line in out.lines() {
+                let Some(rest) = line.trim_start().strip_prefix('[').and_then(|l| l.split_once(']')).map(|(_, r)| r.trim_start()) else { continue; };
+                let (pkgver, summary) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                let Some((package, version)) = split_pkgver(pkgver, 1) else { continue; };
+                hits.push(SearchHit { package, version, summary: summary.trim().to_string() });
+            }
+        }
+        Family::Gentoo => {
+            // "*  category/name" followed by indented "Latest version available:" and "Description:" lines
+            for line in out.lines() {
+                let t = line.trim();
+                if let Some(atom) = line.strip_prefix('*') {
+                    let atom = atom.trim().trim_end_matches("[ Masked ]").trim();
+                    if !atom.is_empty() { hits.push(SearchHit { package: atom.to_string(), ..Default::default() }); }
+                } else if let Some(last) = hits.last_mut() {
+                    if let Some(v) = t.strip_prefix("Latest version available:") { last.version = v.trim().to_string(); }
+                    else if let Some(v) = t.strip_prefix("Description:") { last.summary = v.trim().to_string(); }
+                }
+            }
+        }
+    }
+    // Multiple versions/arches of a package collapse into the first one listed
+    let mut seen = std::collections::HashSet::new();
+    hits.retain(|h| seen.insert(h.package.clone()));
+    hits
+}
+
+/// Split `name-version` at the `dashes`-th dash from the end (apk versions carry a `-rN` release).
+fn split_pkgver(pkgver: &str, dashes: usize) -> Option<(String, String)> {
+    let mut cut = pkgver.len();
+    for _ in 0..dashes { cut = pkgver[..cut].rfind('-')?; }
+    let (name, version) = (&pkgver[..cut], &pkgver[cut + 1..]);
+    if name.is_empty() || version.is_empty() { return None; }
+    Some((name.to_string(), version.to_string()))
+}
+
+/// Command printing the name of the package owning `path` (after resolving it with `command -v`).
+pub fn owner_cmd(fam: Family, path: &str) -> String {
+    let p = shell_escape::escape(std::borrow::Cow::from(path.to_string()));
+    let query = match fam {
+        Family::Debian => "dpkg-query -S \"$f\" 2>/dev/null | head -n1 | cut -d: -f1",
+        Family::Fedora | Family::OpenSuse => "rpm -qf --qf '%{NAME}\\n' \"$f\" 2>/dev/null",
+        Family::Arch => "pacman -Qoq \"$f\" 2>/dev/null",
+        Family::Alpine => "apk info -W \"$f\" 2>/dev/null | sed -n 's/.* is owned by //p' | sed -E 's/-[^-]+-r[0-9]+$//'",
+        Family::Void => "xbps-query -o \"$f\" 2>/dev/null | head -n1 | cut -d: -f1 | sed -E 's/-[^-]+$//'",
+        Family::Gentoo => "grep -lF -e \"obj $f \" -e \"sym $f -> \" /var/db/pkg/*/*/CONTENTS 2>/dev/null | head -n1 | cut -d/ -f5,6 | sed -E 's/-[0-9][^-]*(-r[0-9]+)?$//'",
+    };
+    // Try the path as given, then with symlinks resolved (merged /usr, alternatives)
+    format!(
+        "p=$(command -v {p} 2>/dev/null || echo {p}); for f in \"$p\" \"$(readlink -f \"$p\")\"; do o=$({q}); if [ -n \"$o\" ]; then echo \"$o\"; exit 0; fi; done; exit 1",
+        p = p,
+        q = query
+    )
+}
+
+/// Command printing the installed version of a package.
+pub fn version_cmd(fam: Family, pkg: &str) -> String {
+    let p = shell_escape::escape(std::borrow::Cow::from(pkg.to_string()));
+    match fam {
+        Family::Debian => format!("dpkg-query -W -f='${{Version}}\\n' {}", p),
+        Family::Fedora | Family::OpenSuse => format!("rpm -q --qf '%{{VERSION}}-%{{RELEASE}}\\n' {}", p),
+        Family::Arch => format!("pacman -Q {} | cut -d' ' -f2", p),
+        Family::Alpine | Family::Void | Family::Gentoo => {
+            format!("{} | awk -F'\\t' -v p={} '$1 == p {{ print $2 }}'", list_installed_cmd(fam), p)
+        }
+    }
+}
+
+/// Command printing package metadata, falling back to repository metadata when not installed.
+pub fn info_cmd(fam: Family, pkg: &str) -> String {
+    let p = shell_escape::escape(std::borrow::Cow::from(pkg.to_string()));
+    match fam {
+        Family::Debian => format!("dpkg-query -s {p} 2>/dev/null || apt-cache show {p}", p = p),
+        Family::Fedora => format!("rpm -qi {p} 2>/dev/null || dnf -q info {p}", p = p),
+        Family::OpenSuse => format!("rpm -qi {p} 2>/dev/null || zypper --non-interactive info {p}", p = p),
+        Family::Arch => format!("pacman -Qi {p} 2>/dev/null || pacman -Si {p}", p = p),
+        Family::Alpine => format!("apk info -a {}", p),
+        Family::Void => format!("xbps-query {p} 2>/dev/null || xbps-query -R {p}", p = p),
+        Family::Gentoo => format!("emerge --search {}", p),
+    }
+}
+
+/// Root command that removes `pkg`; `autoremove` also drops dependencies nothing else needs.
+pub fn remove_cmd(fam: Family, pkg: &str, autoremove: bool) -> String {
+    let p = shell_escape::escape(std::borrow::Cow::from(pkg.to_string()));
+    match (fam, autoremove) {
+        (Family::Debian, false) => format!("set -e; if command -v apt-get >/dev/null; then apt-get -y remove {}; else dpkg -r {}; fi", p, p),
+        (Family::Debian, true) => format!("set -e; apt-get -y remove --autoremove {}", p),
+        // Fedora turns clean_requirements_on_remove on by default, so say which one is wanted
+        (Family::Fedora, _) => format!(
+            "set -e; if command -v dnf >/dev/null; then dnf -y remove --setopt=clean_requirements_on_remove={} {}; else rpm -e {}; fi",
+            if autoremove { "True" } else { "False" }, p, p
+        ),
+        (Family::OpenSuse, false) => format!("set -e; if command -v zypper >/dev/null; then zypper --non-interactive rm {}; else rpm -e {}; fi", p, p),
+        (Family::OpenSuse, true) => format!("set -e; zypper --non-interactive rm --clean-deps {}", p),
+        (Family::Arch, false) => format!("set -e; if command -v pacman >/dev/null; then pacman -R --noconfirm {}; else echo 'pacman not found' >&2; exit 1; fi", p),
+        (Family::Arch, true) => format!("set -e; pacman -Rns --noconfirm {}", p),
+        // apk drops orphaned dependencies on every `del`
+        (Family::Alpine, _) => format!("set -e; apk del {}", p),
+        (Family::Void, false) => format!("set -e; xbps-remove -y {}", p),
+        (Family::Void, true) => format!("set -e; xbps-remove -yR {}", p),
+        // Drop it from @world first so depclean is allowed to remove it
+        (Family::Gentoo, false) => format!("set -e; emerge --deselect {p}; emerge --depclean {p}", p = p),
+        (Family::Gentoo, true) => format!("set -e; emerge --deselect {p}; emerge --depclean", p = p),
+    }
+}
+
+/// Root command simulating [`remove_cmd`]; its stdout goes to [`parse_remove_preview`].
+pub fn remove_preview_cmd(fam: Family, pkg: &str, autoremove: bool) -> String {
+    let p = shell_escape::escape(std::borrow::Cow::from(pkg.to_string()));
+    match fam {
+        Family::Debian => format!("apt-get -s remove {}{}", if autoremove { "--autoremove " } else { "" }, p),
+        Family::Fedora => format!(
+            "if command -v dnf >/dev/null; then dnf remove --assumeno --setopt=clean_requirements_on_remove={c} {p}; else yum remove --assumeno {p}; fi",
+            c = if autoremove { "True" } else { "False" }, p = p
+        ),
+        Family::OpenSuse => format!("zypper --non-interactive rm --dry-run {}{}", if autoremove { "--clean-deps " } else { "" }, p),
+        Family::Arch => format!("pacman -Rp{} --print-format %n {}", if autoremove { "ns" } else { "" }, p),
+        Family::Alpine => format!("apk del --simulate {}", p),
+        Family::Void => format!("xbps-remove -n{} {}", if autoremove { "R" } else { "" }, p),
+        // Orphans left by the package itself only show up once it is out of @world
+        Family::Gentoo if autoremove => format!("emerge --pretend --depclean {p}; emerge --pretend --depclean", p = p),
+        Family::Gentoo => format!("emerge --pretend --depclean {}", p),
+    }
+}
+
+/// Names of the packages a [`remove_preview_cmd`] run says would be removed.
+pub fn parse_remove_preview(fam: Family, out: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    let mut in_list = false;
+    for line in out.lines() {
+        let name = match fam {
+            // `Remv name [version]`
+            Family::Debian => line.strip_prefix("Remv ").and_then(|r| r.split_whitespace().next()),
+            // Table sections `Removing:`, `Removing dependent packages:`, `Removing unused dependencies:`
+            Family::Fedora => {
+                if line.starts_with("Removing") && line.trim_end().ends_with(':') { in_list = true; continue; }
+                if !line.starts_with(' ') { in_list = false; }
+                // Wrapped rows continue on lines with deeper indentation
+                if in_list && !line.starts_with("  ") { line.split_whitespace().next() } else { None }
+            }
+            // `The following N packages are going to be REMOVED:` then indented names, several per line
+            Family::OpenSuse => {
+                if line.contains("going to be REMOVED") { in_list = true; continue; }
+                if line.trim().is_empty() { in_list = false; }
+                if in_list { names.extend(line.split_whitespace().map(str::to_string)); }
+                None
+            }
+            Family::Arch => Some(line.trim()).filter(|l| !l.is_empty() && !l.contains(' ')),
+            // `(1/2) Purging name (version)`
+            Family::Alpine => line.split_once("Purging ").and_then(|(_, r)| r.split_whitespace().next()),
+            // `name-version remove arch repo size`
+            Family::Void => {
+                let mut words = line.split_whitespace();
+                match (words.next(), words.next()) {
+                    (Some(pkgver), Some("remove")) => pkgver.rsplit_once('-').map(|(n, _)| n),
+                    _ => None,
+                }
+            }
+            // `>>> These are the packages that would be unmerged:` then ` category/name` rows
+            Family::Gentoo => {
+                if line.contains("would be unmerged") { in_list = true; continue; }
+                if line.starts_with(">>>") || line.starts_with("Number") { in_list = false; }
+                let t = line.trim();
+                if in_list && line.starts_with(' ') && !line.starts_with("  ") && t.contains('/') { Some(t) } else { None }
+            }
+        };
+        if let Some(n) = name { names.push(n.to_string()); }
+    }
+    let mut seen = std::collections::HashSet::new();
+    names.retain(|n| seen.insert(n.clone()));
+    names
+}
+
+/// Root command that stops upgrades of an installed package with the family's native lock.
+pub fn hold_cmd(fam: Family, pkg: &str) -> String {
+    let script = match fam {
+        Family::Debian => "apt-mark hold {{pkg}}",
+        // versionlock is a plugin on dnf4 and built in on dnf5; install it on first use
+        Family::Fedora => "set -e; if ! dnf versionlock add {{pkg}}; then dnf -y install 'dnf-command(versionlock)'; dnf versionlock add {{pkg}}; fi",
+        Family::OpenSuse => "zypper --non-interactive addlock {{pkg}}",
+        Family::Arch => r#"set -e; p={{pkg}}; f=/etc/pacman.conf
+grep -qxF "IgnorePkg = $p" "$f" || { awk -v p="$p" '{ print } /^\[options\]/ { print "IgnorePkg = " p }' "$f" > "$f.new"; mv "$f.new" "$f"; }"#,
+        // Pin the world entry to the installed version
+        Family::Alpine => r#"set -e; p={{pkg}}
+v=$(apk info -v 2>/dev/null | awk -v p="$p" '{ n = $0; sub(/-[^-]+-r[0-9]+$/, "", n); if (n == p) print substr($0, length(n) + 2) }')
+[ -n "$v" ] || { echo "$p is not installed" >&2; exit 1; }
+apk add "$p=$v""#,
+        Family::Void => "xbps-pkgdb -m hold {{pkg}}",
+        // Mask every version newer than the installed one
+        Family::Gentoo => r#"set -e; p={{pkg}}; f=/etc/portage/package.mask/pkgbridge
+d=$(ls -d /var/db/pkg/*/"$p"-[0-9]* 2>/dev/null | head -n 1)
+[ -n "$d" ] || { echo "$p is not installed" >&2; exit 1; }
+mkdir -p /etc/portage/package.mask; a=">${d#/var/db/pkg/}"
+grep -qxF "$a" "$f" 2>/dev/null || echo "$a" >> "$f""#,
+    };
+    crate::quote::sh_template(script, &[("pkg", pkg)])
+}
+
+/// Root command that releases a lock added by `hold_cmd`.
+pub fn unhold_cmd(fam: Family, pkg: &str) -> String {
+    let script = match fam {
+        Family::Debian => "apt-mark unhold {{pkg}}",
+        Family::Fedora => "dnf versionlock delete {{pkg}}",
+        Family::OpenSuse => "zypper --non-interactive removelock {{pkg}}",
+        Family::Arch => r#"set -e; p={{pkg}}; f=/etc/pacman.conf
+awk -v p="$p" '$0 != "IgnorePkg = " p' "$f" > "$f.new"; mv "$f.new" "$f""#,
+        // Re-adding the bare name replaces the `pkg=version` world entry
+        Family::Alpine => "apk add {{pkg}}",
+        Family::Void => "xbps-pkgdb -m unhold {{pkg}}",
+        Family::Gentoo => r#"set -e; p={{pkg}}; f=/etc/portage/package.mask/pkgbridge
+[ -f "$f" ] || exit 0
+awk -v p="$p" '{ a = $0; sub(/^>[^\/]*\//, "", a); sub(/-[0-9][^-]*(-r[0-9]+)?$/, "", a); if (a != p) print }' "$f" > "$f.new"; mv "$f.new" "$f""#,
+    };
+    crate::quote::sh_template(script, &[("pkg", pkg)])
+}
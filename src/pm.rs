@@ -1,119 +1,608 @@
 use crate::distro::Family;
 use crate::config;
-use anyhow::{Context, Result};
+use crate::quote;
+use crate::dryrun;
+use anyhow::{anyhow, Context, Result};
 use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use which::which;
 
 pub fn set_default(fam: Family, box_name: &str) -> Result<()> {
-    let mut cfg = config::load_config();
+    let mut cfg = config::load_config()?;
     cfg.pm_defaults.insert(family_key(fam).into(), box_name.to_string());
     config::save_config(&cfg)
 }
 
-pub fn show_defaults() -> HashMap<String, String> {
-    config::load_config().pm_defaults
+pub fn show_defaults() -> Result<HashMap<String, String>> {
+    Ok(config::load_config()?.pm_defaults)
 }
 
-pub fn generate_shims() -> Result<()> {
-    let cfg = config::load_config();
-    let home = std::env::var("HOME").unwrap_or_else(|_| ".".into());
-    let bindir: PathBuf = std::env::var("XDG_BIN_HOME").map(PathBuf::from).unwrap_or_else(|_| PathBuf::from(format!("{home}/.local/bin")));
-    fs::create_dir_all(&bindir).ok();
-    // Also create bootstrap shims for missing managers if no defaults exist yet
-    generate_bootstrap_shims_into(&bindir)?;
-    for (fam_key, box_name) in cfg.pm_defaults.iter() {
-        match fam_key.as_str() {
-            // Treat Ubuntu the same as Debian for apt-based shims
-            "debian" | "ubuntu" => {
-                generate_shim_with_policy(&bindir, "apt", box_name, fam_key)?;
-                generate_shim_with_policy(&bindir, "apt-get", box_name, fam_key)?;
-            }
-            "fedora" => {
-                generate_shim_with_policy(&bindir, "dnf", box_name, fam_key)?;
-            }
-            "opensuse" => {
-                generate_shim_with_policy(&bindir, "zypper", box_name, fam_key)?;
+/// How a host shim runs one package manager inside its family's default box.
+#[derive(Debug)]
+pub struct ManagerDef {
+    /// Command name, which is also the shim's file name
+    pub name: &'static str,
+    pub family: Family,
+    /// Run through the box user's sudo/doas rather than as the user (AUR helpers build as the
+    /// user and call sudo themselves)
+    pub elevate: bool,
+    /// Operations are words (`apt install`) rather than flags (`pacman -S`, `rpm -i`)
+    pub word_subcommands: bool,
+    /// Global options that take a separate value, so the value is not mistaken for a subcommand
+    pub value_opts: &'static [&'static str],
+    /// Subcommands and options that only read: no elevation, snapshot or export.
+    /// Short options also match with flags clustered after them (`-Ss` covers `-Ssq`).
+    pub read_only: &'static [&'static str],
+    /// Subcommands (or operation flags) after which new or upgraded packages are exported;
+    /// empty means every call that is not read-only
+    pub post_tx: &'static [&'static str],
+    /// What a call without arguments does (`yay` alone upgrades the system)
+    pub bare: CallKind,
+}
+
+/// What a package-manager call needs around it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallKind {
+    /// Only reads the box: run as the user
+    ReadOnly,
+    /// Changes the box without installing anything (refreshing indexes, removing)
+    Mutating,
+    /// May install or upgrade packages: snapshot before, export after
+    Transaction,
+}
+
+impl ManagerDef {
+    /// Classify a call by its subcommand, or by its operation flags for flag-driven managers.
+    pub fn classify(&self, args: &[String]) -> CallKind {
+        let mut subcommand: Option<&str> = None;
+        let mut transaction = self.post_tx.is_empty();
+        let mut it = args.iter().map(String::as_str);
+        while let Some(arg) = it.next() {
+            if arg == "--" { break; }
+            if self.value_opts.contains(&arg) { it.next(); continue; }
+            if !arg.starts_with('-') {
+                // Only the first word is the subcommand; later ones are packages
+                if self.word_subcommands && subcommand.is_none() {
+                    if self.read_only.contains(&arg) { return CallKind::ReadOnly; }
+                    transaction |= self.post_tx.contains(&arg);
+                    subcommand = Some(arg);
+                }
+                continue;
             }
-            "arch" => {
-                generate_shim_with_policy(&bindir, "pacman", box_name, fam_key)?;
+            // Options are checked wherever they appear: `apt-get install -s` only simulates
+            if arg == "--help" || arg == "--version" || self.read_only.iter().any(|o| option_matches(o, arg)) {
+                return CallKind::ReadOnly;
             }
-            _ => {}
+            if !self.word_subcommands && self.post_tx.iter().any(|o| option_matches(o, arg)) { transaction = true; }
         }
+        // Without an operation the manager only prints its usage
+        if args.is_empty() { return self.bare; }
+        if self.word_subcommands && subcommand.is_none() { return CallKind::ReadOnly; }
+        if transaction { CallKind::Transaction } else { CallKind::Mutating }
+    }
+}
+
+/// Whether `arg` selects option `opt`: long options exactly or as `--opt=value`, short ones
+/// also with more flags clustered after them (`-q` matches `-qa`). Words never match.
+fn option_matches(opt: &str, arg: &str) -> bool {
+    if let Some(long) = opt.strip_prefix("--") {
+        return arg.strip_prefix("--").and_then(|a| a.strip_prefix(long)).is_some_and(|r| r.is_empty() || r.starts_with('='));
+    }
+    opt.starts_with('-') && !arg.starts_with("--") && arg.starts_with(opt)
+}
+
+const APT_VALUE_OPTS: &[&str] = &["-o", "--option", "-c", "--config-file", "-t", "--target-release", "-a", "--host-architecture"];
+const APT_POST_TX: &[&str] = &["install", "reinstall", "upgrade", "full-upgrade", "dist-upgrade", "dselect-upgrade", "build-dep"];
+const DNF_VALUE_OPTS: &[&str] = &[
+    "-c", "--config", "--installroot", "--releasever", "--repo", "--repoid", "--enablerepo", "--disablerepo", "--setopt", "-x", "--exclude",
+    "-d", "--debuglevel", "-e", "--errorlevel", "--forcearch", "--color", "--destdir", "--downloaddir",
+];
+const DNF_READ_ONLY: &[&str] = &[
+    "-h", "--assumeno", "search", "se", "info", "if", "list", "ls", "provides", "whatprovides", "repoquery", "rq", "deplist", "repolist",
+    "repoinfo", "check-update", "check-upgrade", "updateinfo", "advisory", "help", "download",
+];
+const DNF_POST_TX: &[&str] = &[
+    "install", "in", "reinstall", "rei", "upgrade", "up", "update", "distro-sync", "dsync", "downgrade", "dg", "swap", "localinstall",
+    "group", "groupinstall", "module",
+];
+const RPM_VALUE_OPTS: &[&str] = &["--root", "-r", "--dbpath", "--rcfile", "--define", "-D", "--macros"];
+const PACMAN_VALUE_OPTS: &[&str] = &[
+    "-r", "--root", "-b", "--dbpath", "--cachedir", "--config", "--arch", "--gpgdir", "--hookdir", "--logfile", "--sysroot", "--color",
+    "--assume-installed", "--ignore", "--ignoregroup", "--overwrite",
+];
+/// pacman's queries plus the helpers' own: fetching PKGBUILDs (`-G`) and statistics (`-P`).
+const AUR_HELPER_READ_ONLY: &[&str] = &[
+    "-V", "-h", "-Q", "--query", "-T", "--deptest", "-Ss", "-Si", "-Sl", "-Sg", "-Sp", "-Fl", "-Fx", "-Fq", "-Fs", "--print", "-G",
+    "--getpkgbuild", "-P", "--show",
+];
+
+/// Package managers pkgbridge writes shims for; the first entry of a family is its main manager.
+pub const MANAGERS: &[ManagerDef] = &[
+    ManagerDef {
+        name: "apt",
+        family: Family::Debian,
+        elevate: true,
+        word_subcommands: true,
+        value_opts: APT_VALUE_OPTS,
+        read_only: &[
+            "-v", "-h", "-s", "--simulate", "--dry-run", "--just-print", "--no-act", "search", "show", "list", "policy", "depends",
+            "rdepends", "showsrc", "changelog", "madison", "download", "help",
+        ],
+        post_tx: APT_POST_TX,
+        bare: CallKind::ReadOnly,
+    },
+    ManagerDef {
+        name: "apt-get",
+        family: Family::Debian,
+        elevate: true,
+        word_subcommands: true,
+        value_opts: APT_VALUE_OPTS,
+        read_only: &["-v", "-h", "-s", "--simulate", "--dry-run", "--just-print", "--no-act", "changelog", "download", "source", "help"],
+        post_tx: APT_POST_TX,
+        bare: CallKind::ReadOnly,
+    },
+    ManagerDef {
+        name: "dpkg",
+        family: Family::Debian,
+        elevate: true,
+        word_subcommands: false,
+        value_opts: &["--root", "--admindir", "--instdir"],
+        read_only: &[
+            "-l", "--list", "-L", "--listfiles", "-s", "--status", "-S", "--search", "-p", "--print-avail", "-C", "--audit",
+            "--get-selections", "--print-architecture", "--compare-versions", "--no-act", "--dry-run", "--simulate", "-c", "--contents",
+            "-I", "--info", "-f", "--field", "-W", "--show",
+        ],
+        post_tx: &["-i", "--install", "--unpack", "--configure"],
+        bare: CallKind::ReadOnly,
+    },
+    ManagerDef {
+        name: "dnf",
+        family: Family::Fedora,
+        elevate: true,
+        word_subcommands: true,
+        value_opts: DNF_VALUE_OPTS,
+        read_only: DNF_READ_ONLY,
+        post_tx: DNF_POST_TX,
+        bare: CallKind::ReadOnly,
+    },
+    ManagerDef {
+        name: "yum",
+        family: Family::Fedora,
+        elevate: true,
+        word_subcommands: true,
+        value_opts: DNF_VALUE_OPTS,
+        read_only: DNF_READ_ONLY,
+        post_tx: DNF_POST_TX,
+        bare: CallKind::ReadOnly,
+    },
+    ManagerDef {
+        name: "rpm",
+        family: Family::Fedora,
+        elevate: true,
+        word_subcommands: false,
+        value_opts: RPM_VALUE_OPTS,
+        read_only: &["-q", "--query", "-V", "--verify", "-K", "--checksig", "--test", "--eval", "-E", "--showrc", "--querytags"],
+        post_tx: &["-i", "--install", "-U", "--upgrade", "-F", "--freshen", "--reinstall"],
+        bare: CallKind::ReadOnly,
+    },
+    ManagerDef {
+        name: "zypper",
+        family: Family::OpenSuse,
+        elevate: true,
+        word_subcommands: true,
+        value_opts: &["-R", "--root", "-c", "--config", "-D", "--reposd-dir", "-C", "--cache-dir", "--raw-cache-dir", "--solv-cache-dir", "--pkg-cache-dir"],
+        read_only: &[
+            "-V", "-h", "--dry-run", "search", "se", "info", "if", "list-updates", "lu", "list-patches", "lp", "packages", "pa",
+            "patterns", "pt", "products", "pd", "repos", "lr", "services", "ls", "patches", "what-provides", "wp", "search-packages",
+            "patch-check", "pchk", "locks", "ll", "help",
+        ],
+        post_tx: &["install", "in", "update", "up", "dist-upgrade", "dup", "patch", "source-install", "si", "install-new-recommends", "inr"],
+        bare: CallKind::ReadOnly,
+    },
+    ManagerDef {
+        name: "pacman",
+        family: Family::Arch,
+        elevate: true,
+        word_subcommands: false,
+        value_opts: PACMAN_VALUE_OPTS,
+        // -Q and -T never change anything; -S only with its query modifiers
+        read_only: &[
+            "-V", "-h", "-Q", "--query", "-T", "--deptest", "-Ss", "-Si", "-Sl", "-Sg", "-Sp", "-Fl", "-Fx", "-Fq", "-Fs", "--print",
+        ],
+        post_tx: &["-S", "--sync", "-U", "--upgrade"],
+        bare: CallKind::ReadOnly,
+    },
+    ManagerDef {
+        name: "yay",
+        family: Family::Arch,
+        elevate: false,
+        word_subcommands: false,
+        value_opts: PACMAN_VALUE_OPTS,
+        read_only: AUR_HELPER_READ_ONLY,
+        post_tx: &[],
+        bare: CallKind::Transaction,
+    },
+    ManagerDef {
+        name: "paru",
+        family: Family::Arch,
+        elevate: false,
+        word_subcommands: false,
+        value_opts: PACMAN_VALUE_OPTS,
+        read_only: AUR_HELPER_READ_ONLY,
+        post_tx: &[],
+        bare: CallKind::Transaction,
+    },
+    ManagerDef {
+        name: "apk",
+        family: Family::Alpine,
+        elevate: true,
+        word_subcommands: true,
+        value_opts: &["-X", "--repository", "-p", "--root", "--arch", "--cache-dir", "--keys-dir", "--repositories-file"],
+        read_only: &["-V", "-h", "-s", "--simulate", "search", "info", "list", "policy", "dot", "stats", "audit", "verify", "help"],
+        post_tx: &["add", "upgrade", "fix"],
+        bare: CallKind::ReadOnly,
+    },
+    ManagerDef {
+        name: "xbps-install",
+        family: Family::Void,
+        elevate: true,
+        word_subcommands: false,
+        value_opts: &["-C", "--config", "-c", "--cachedir", "-r", "--rootdir", "-R", "--repository"],
+        read_only: &["-V", "-h", "-n", "--dry-run"],
+        post_tx: &[],
+        bare: CallKind::ReadOnly,
+    },
+    ManagerDef {
+        name: "emerge",
+        family: Family::Gentoo,
+        elevate: true,
+        word_subcommands: false,
+        value_opts: &["--jobs", "--load-average", "--root", "--config-root", "--exclude", "--usepkg-exclude"],
+        read_only: &["-V", "-h", "--search", "-s", "--searchdesc", "-S", "--info", "--pretend", "-p", "--check-news"],
+        post_tx: &[],
+        bare: CallKind::ReadOnly,
+    },
+];
+
+/// Definition of a shimmed package manager by command name.
+pub fn manager(name: &str) -> Option<&'static ManagerDef> {
+    MANAGERS.iter().find(|m| m.name == name)
+}
+
+/// The manager `pm exec <family>` runs when given a family rather than a manager name.
+pub fn family_manager(f: Family) -> &'static ManagerDef {
+    MANAGERS.iter().find(|m| m.family == f).expect("every family has a package manager")
+}
+
+/// Configured default box for a family (`ubuntu` still counts for Debian).
+pub fn default_box(f: Family) -> Result<Option<String>> {
+    let defaults = show_defaults()?;
+    Ok(defaults.get(family_key(f)).cloned()
+        .or_else(|| if f == Family::Debian { defaults.get("ubuntu").cloned() } else { None }))
+}
+
+pub fn generate_shims() -> Result<()> {
+    let bindir = bindir();
+    dryrun::create_dir_all(&bindir).ok();
+    for def in MANAGERS {
+        generate_shim_with_policy(&bindir, def.name, def.family)?;
     }
     // Ensure the bin dir is on PATH for common shells (fish gets an auto-conf.d drop-in)
     ensure_bindir_on_path(&bindir)?;
     Ok(())
 }
 
-pub fn write_shim(dir: &PathBuf, wrapper_name: &str, inner_cmd: &str, box_name: &str, fam_key: &str) -> Result<()> {
-    let path = dir.join(wrapper_name);
-    // Never use container root; prefer user + sudo/doas. This forwards password prompts to the host terminal.
-    let content = format!("#!/usr/bin/env sh\nset -e\nbox=\"{}\"\nfam=\"{}\"\n# Fast-path readonly queries without sudo to avoid prompts\ncase \"$1\" in\n  --version|-v|--help|-h)\n    exec distrobox enter -n \"$box\" -- {} \"$@\";;\n  *) ;;\nesac\n# Pre-transaction snapshot\npkgbridge pm snapshot --family \"$fam\" --container \"$box\" >/dev/null 2>&1 || true\nstatus=0\n# Run inside container as user; prefer sudo, then doas, else non-root (may fail)\nif distrobox enter -n \"$box\" -- sh -lc 'command -v sudo >/dev/null' >/dev/null 2>&1; then\n  # If passwordless sudo works, great; otherwise allow interactive prompt\n  if distrobox enter -n \"$box\" -- sudo -n true >/dev/null 2>&1; then\n    distrobox enter -n \"$box\" -- sudo {} \"$@\" || status=$?\n  else\n    distrobox enter -n \"$box\" -- sudo {} \"$@\" || status=$?\n  fi\nelif distrobox enter -n \"$box\" -- sh -lc 'command -v doas >/dev/null' >/dev/null 2>&1; then\n  distrobox enter -n \"$box\" -- doas {} \"$@\" || status=$?\nelse\n  distrobox enter -n \"$box\" -- {} \"$@\" || status=$?\nfi\n# Post-transaction export\npkgbridge pm post-transaction --family \"$fam\" --container \"$box\" >/dev/null 2>&1 || true\nexit $status\n", box_name, fam_key, inner_cmd, inner_cmd, inner_cmd, inner_cmd, inner_cmd);
-    fs::write(&path, content).with_context(|| format!("writing {}", path.display()))?;
-    let mut perms = fs::metadata(&path)?.permissions();
+/// Shims resolve the box at run time, so changing a default never requires rewriting them.
+const SHIM_TEMPLATE: &str = "exec pkgbridge pm exec {{mgr}} -- \"$@\"\n";
+
+pub fn write_shim(dir: &Path, wrapper_name: &str, mgr: &str, fam: Family) -> Result<()> {
+    let body = quote::sh_template(SHIM_TEMPLATE, &[("mgr", mgr)]);
+    write_stamped(&dir.join(wrapper_name), &body, mgr, family_key(fam), None)
+}
+
+/// Header line prefix marking files written by pkgbridge as package-manager shims.
+const STAMP: &str = "# pkgbridge-shim-";
+
+/// Metadata read back from a shim's header.
+#[derive(Debug, Clone)]
+pub struct ShimStamp {
+    pub version: String,
+    pub manager: String,
+    pub family: String,
+    /// Box pinned by older shims; `None` for shims that resolve the default at run time
+    pub box_name: Option<String>,
+    pub checksum: String,
+    /// Whether the body still matches the recorded checksum
+    pub intact: bool,
+    /// Written by a release before shims were stamped
+    pub legacy: bool,
+}
+
+fn body_checksum(body: &str) -> String {
+    use sha2::{Digest, Sha256};
+    Sha256::digest(body.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Write a shim with its stamp header, make it executable and record it in the manifest.
+fn write_stamped(path: &Path, body: &str, manager: &str, fam_key: &str, box_name: Option<&str>) -> Result<()> {
+    let checksum = body_checksum(body);
+    // Header values stay on one line whatever the box is called
+    let one_line = |v: &str| v.replace(['\n', '\r'], " ");
+    let mut content = String::from("#!/usr/bin/env sh\n");
+    content.push_str(&format!("{}version: {}\n", STAMP, env!("CARGO_PKG_VERSION")));
+    content.push_str(&format!("{}manager: {}\n", STAMP, one_line(manager)));
+    content.push_str(&format!("{}family: {}\n", STAMP, fam_key));
+    content.push_str(&format!("{}box: {}\n", STAMP, box_name.map(one_line).unwrap_or_else(|| "(default)".into())));
+    content.push_str(&format!("{}checksum: sha256:{}\n", STAMP, checksum));
+    content.push_str(body);
+    dryrun::write(path, content).with_context(|| format!("writing {}", path.display()))?;
     #[cfg(unix)] {
         use std::os::unix::fs::PermissionsExt;
-        perms.set_mode(0o755);
-        fs::set_permissions(&path, perms)?;
+        dryrun::set_permissions(path, fs::Permissions::from_mode(0o755))?;
+    }
+    let mut st = config::load_state()?;
+    st.shims.insert(path.to_string_lossy().to_string(), config::ShimRecord {
+        manager: manager.to_string(),
+        family: fam_key.to_string(),
+        box_name: box_name.map(|b| b.to_string()),
+        checksum,
+    });
+    config::save_state(&st)
+}
+
+/// Parse the stamp header of a pkgbridge shim; `None` for any other file.
+pub fn read_stamp(path: &Path) -> Option<ShimStamp> {
+    let meta = fs::metadata(path).ok()?;
+    if !meta.is_file() || meta.len() > 256 * 1024 { return None; }
+    let content = fs::read_to_string(path).ok()?;
+    let mut fields: HashMap<&str, &str> = HashMap::new();
+    let mut body_start = 0;
+    for (i, line) in content.split_inclusive('\n').enumerate() {
+        if i == 0 && line.starts_with("#!") { body_start += line.len(); continue; }
+        let Some(rest) = line.strip_prefix(STAMP) else { break; };
+        if let Some((k, v)) = rest.trim_end().split_once(": ") { fields.insert(k, v); }
+        body_start += line.len();
+    }
+    let Some(checksum) = fields.get("checksum").and_then(|c| c.strip_prefix("sha256:")).map(str::to_string) else {
+        return legacy_stamp(&content);
+    };
+    let intact = body_checksum(&content[body_start..]) == checksum;
+    Some(ShimStamp {
+        version: fields.get("version").unwrap_or(&"").to_string(),
+        manager: fields.get("manager").unwrap_or(&"").to_string(),
+        family: fields.get("family").unwrap_or(&"").to_string(),
+        box_name: fields.get("box").filter(|b| **b != "(default)").map(|b| b.to_string()),
+        checksum,
+        intact,
+        legacy: false,
+    })
+}
+
+/// Shims from before stamps existed pinned their box in a `box="..."` line and took snapshots
+/// through `pkgbridge pm snapshot --family`; they are pkgbridge's to replace.
+fn legacy_stamp(content: &str) -> Option<ShimStamp> {
+    if !content.contains("pkgbridge pm snapshot --family") { return None; }
+    let var = |k: &str| content.lines().find_map(|l| l.strip_prefix(k)?.strip_prefix("=\"")?.strip_suffix('"')).map(str::to_string);
+    let box_name = var("box")?;
+    let manager = content
+        .lines()
+        .find_map(|l| l.trim().strip_prefix("exec distrobox enter -n \"$box\" -- ")?.split(" \"$@\"").next())
+        .unwrap_or_default()
+        .to_string();
+    Some(ShimStamp {
+        version: "-".into(),
+        manager,
+        family: var("fam").unwrap_or_default(),
+        box_name: Some(box_name),
+        checksum: String::new(),
+        intact: true,
+        legacy: true,
+    })
+}
+
+/// A path pkgbridge may (re)write: nothing there yet, or an unmodified pkgbridge shim.
+fn may_overwrite(path: &Path) -> bool {
+    if !path.exists() { return true; }
+    read_stamp(path).map(|s| s.intact).unwrap_or(false)
+}
+
+fn bindir() -> PathBuf {
+    let home = std::env::var("HOME").unwrap_or_else(|_| ".".into());
+    std::env::var("XDG_BIN_HOME").map(PathBuf::from).unwrap_or_else(|_| PathBuf::from(format!("{home}/.local/bin")))
+}
+
+/// Health of one shim as reported by `pm list-shims`/`pm verify`.
+fn shim_status(path: &Path, stamp: Option<&ShimStamp>, defaults: &HashMap<String, String>) -> &'static str {
+    let Some(stamp) = stamp else {
+        return if path.exists() { "replaced (not a pkgbridge shim)" } else { "missing" };
+    };
+    if !stamp.intact { return "modified"; }
+    if stamp.legacy { return "legacy (pinned to a box by an older release)"; }
+    match &stamp.box_name {
+        Some(b) if defaults.get(&stamp.family) != Some(b) => "stale (box is not the family default)",
+        _ => "ok",
+    }
+}
+
+/// Shims pkgbridge knows about: manifest entries plus stamped files in the bin dir.
+fn known_shims() -> Result<Vec<PathBuf>> {
+    let mut paths: Vec<PathBuf> = config::load_state()?.shims.keys().map(PathBuf::from).collect();
+    if let Ok(rd) = fs::read_dir(bindir()) {
+        for p in rd.filter_map(|e| e.ok()).map(|e| e.path()) {
+            if !paths.contains(&p) && read_stamp(&p).is_some() { paths.push(p); }
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+pub fn list_shims() -> Result<()> {
+    let defaults = show_defaults()?;
+    let paths = known_shims()?;
+    if paths.is_empty() {
+        println!("No pkgbridge shims found. Run: pkgbridge pm generate-shims");
+        return Ok(());
+    }
+    for p in paths {
+        let stamp = read_stamp(&p);
+        let name = p.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        let (mgr, fam, bx, ver) = match &stamp {
+            Some(s) => (s.manager.clone(), s.family.clone(), s.box_name.clone().unwrap_or_else(|| "(default)".into()), s.version.clone()),
+            None => ("-".into(), "-".into(), "-".into(), "-".into()),
+        };
+        println!("{}\t{}\t{}\t{}\tv{}\t{}", name, mgr, fam, bx, ver, shim_status(&p, stamp.as_ref(), &defaults));
     }
     Ok(())
 }
 
+/// Check every known shim; errors when any is missing, modified, replaced or stale.
+pub fn verify_shims() -> Result<()> {
+    let defaults = show_defaults()?;
+    let manifest = config::load_state()?.shims;
+    let mut problems = 0usize;
+    for p in known_shims()? {
+        let stamp = read_stamp(&p);
+        let mut status = shim_status(&p, stamp.as_ref(), &defaults).to_string();
+        if status == "ok" {
+            match (manifest.get(p.to_string_lossy().as_ref()), &stamp) {
+                (None, _) => status = "untracked (not in manifest)".into(),
+                (Some(rec), Some(s)) if rec.checksum != s.checksum => status = "differs from manifest".into(),
+                _ => {}
+            }
+        }
+        if status != "ok" { problems += 1; }
+        println!("{}: {}", p.display(), status);
+    }
+    if problems > 0 {
+        return Err(anyhow!("{} shim(s) need attention; 'pkgbridge pm regenerate' rewrites pkgbridge-owned ones", problems));
+    }
+    Ok(())
+}
+
+/// Drop unmodified shims bound to boxes that are no longer defaults and ones from older releases, then write fresh ones.
+pub fn regenerate_shims(force: bool) -> Result<()> {
+    let defaults = show_defaults()?;
+    for p in known_shims()? {
+        let Some(stamp) = read_stamp(&p) else { continue; };
+        if !stamp.intact && !force {
+            println!("Skipping modified shim {} (use --force to overwrite)", p.display());
+            continue;
+        }
+        if !stamp.intact || stamp.legacy { remove_shim_file(&p)?; continue; }
+        if let Some(b) = &stamp.box_name {
+            if defaults.get(&stamp.family) != Some(b) { remove_shim_file(&p)?; }
+        }
+    }
+    generate_shims()
+}
+
+/// Remove pkgbridge-owned shims; modified ones only with `force`.
+pub fn remove_shims(force: bool) -> Result<()> {
+    let mut kept = 0usize;
+    for p in known_shims()? {
+        match read_stamp(&p) {
+            Some(s) if s.intact || force => remove_shim_file(&p)?,
+            Some(_) => { kept += 1; println!("Keeping modified shim {} (use --force to remove)", p.display()); }
+            None => {
+                // Replaced by something else: just forget it
+                let mut st = config::load_state()?;
+                st.shims.remove(p.to_string_lossy().as_ref());
+                config::save_state(&st)?;
+            }
+        }
+    }
+    if kept == 0 && !dryrun::enabled() { println!("Removed pkgbridge shims."); }
+    Ok(())
+}
+
+fn remove_shim_file(path: &Path) -> Result<()> {
+    match dryrun::remove_file(path) {
+        Ok(()) => if !dryrun::enabled() { println!("Removed {}", path.display()) },
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e).with_context(|| format!("removing {}", path.display())),
+    }
+    let mut st = config::load_state()?;
+    st.shims.remove(path.to_string_lossy().as_ref());
+    config::save_state(&st)
+}
+
 pub fn family_key(f: Family) -> &'static str {
-    match f { Family::Debian => "debian", Family::Fedora => "fedora", Family::OpenSuse => "opensuse", Family::Arch => "arch" }
+    match f {
+        Family::Debian => "debian",
+        Family::Fedora => "fedora",
+        Family::OpenSuse => "opensuse",
+        Family::Arch => "arch",
+        Family::Alpine => "alpine",
+        Family::Void => "void",
+        Family::Gentoo => "gentoo",
+    }
+}
+
+/// Inverse of [`family_key`]; accepts `ubuntu` as an alias for the Debian family.
+pub fn family_from_key(key: &str) -> Option<Family> {
+    match key {
+        "debian" | "ubuntu" => Some(Family::Debian),
+        "fedora" => Some(Family::Fedora),
+        "opensuse" => Some(Family::OpenSuse),
+        "arch" => Some(Family::Arch),
+        "alpine" => Some(Family::Alpine),
+        "void" => Some(Family::Void),
+        "gentoo" => Some(Family::Gentoo),
+        _ => None,
+    }
 }
 
-fn generate_shim_with_policy(bindir: &PathBuf, name: &str, box_name: &str, fam_key: &str) -> Result<()> {
+fn generate_shim_with_policy(bindir: &Path, name: &str, fam: Family) -> Result<()> {
     // If the host already has this package manager (and it's not our own shim in bindir),
-    // avoid overshadowing it. Instead, create a suffixed wrapper like "apt-<box>".
-    let host_has = host_has_cmd_outside_bindir(name, bindir);
+    // avoid overshadowing it. Instead, create a suffixed wrapper like "apt-debian".
     let target = bindir.join(name);
-    if host_has {
-        let alt = format!("{}-{}", name, sanitize(box_name));
-        let alt_path = bindir.join(&alt);
-        if alt_path.exists() {
-            // Nothing to do; do not overwrite existing
+    let alt = format!("{}-{}", name, family_key(fam));
+    if host_has_cmd_outside_bindir(name, bindir) {
+        // Only worth a suffixed wrapper once the family has a box to run in
+        if default_box(fam)?.is_none() { return Ok(()); }
+        if !may_overwrite(&bindir.join(&alt)) {
+            println!("'{}' was modified or is not a pkgbridge shim; leaving it alone", alt);
             return Ok(());
         }
-        write_shim(bindir, &alt, name, box_name, fam_key)?;
-        println!("Host has '{}'; created '{}' instead", name, alt);
+        write_shim(bindir, &alt, name, fam)?;
+        if !dryrun::enabled() { println!("Host has '{}'; wrote '{}' instead", name, alt); }
         return Ok(());
     }
     // Host doesn't have this manager on PATH (or only our own bindir entry): prefer unsuffixed name.
-    if target.exists() {
-        // Do not overwrite existing file; also provide suffixed variant for clarity
-        let alt = format!("{}-{}", name, sanitize(box_name));
-        let alt_path = bindir.join(&alt);
-        if !alt_path.exists() {
-            write_shim(bindir, &alt, name, box_name, fam_key)?;
-            println!("'{}' exists; created '{}' as well", name, alt);
+    if !may_overwrite(&target) {
+        // Never overwrite user files; provide a suffixed variant instead
+        if may_overwrite(&bindir.join(&alt)) {
+            write_shim(bindir, &alt, name, fam)?;
+            if !dryrun::enabled() { println!("'{}' is not a pkgbridge shim; wrote '{}' as well", name, alt); }
         }
         return Ok(());
     }
-    write_shim(bindir, name, name, box_name, fam_key)
+    write_shim(bindir, name, name, fam)
 }
 
-fn host_has_cmd_outside_bindir(cmd: &str, bindir: &PathBuf) -> bool {
+fn host_has_cmd_outside_bindir(cmd: &str, bindir: &Path) -> bool {
     match which(cmd) {
         Ok(path) => {
             // If resolved path is inside our bindir, treat as not a host tool
             let rp = path.canonicalize().unwrap_or(path);
-            let rb = bindir.canonicalize().unwrap_or(bindir.clone());
+            let rb = bindir.canonicalize().unwrap_or(bindir.to_path_buf());
             !rp.starts_with(&rb)
         }
         Err(_) => false,
     }
 }
 
-fn sanitize(s: &str) -> String {
-    s.chars()
-        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '-' })
-        .collect()
+/// First line of the block `ensure_bindir_on_path` appends to `~/.profile`.
+const PROFILE_MARKER: &str = "# pkgbridge: add user bin to PATH";
+/// First line of the fish conf.d snippet.
+const FISH_HEADER: &str = "# Added by pkgbridge: ensure user bin dir on PATH";
+
+fn fish_snippet_path(home: &str) -> PathBuf {
+    PathBuf::from(format!("{home}/.config/fish/conf.d/pkgbridge.fish"))
 }
 
-fn ensure_bindir_on_path(bindir: &PathBuf) -> Result<()> {
+fn ensure_bindir_on_path(bindir: &Path) -> Result<()> {
     let bindir_str = bindir.to_string_lossy().to_string();
     // If already present, nothing to do
     if std::env::var_os("PATH")
@@ -128,91 +617,56 @@ fn ensure_bindir_on_path(bindir: &PathBuf) -> Result<()> {
     // Prefer a safe, shell-specific approach
     if shell.ends_with("fish") || std::env::var("FISH_VERSION").is_ok() {
         // Drop a conf.d snippet that adds the path for all sessions
-        let confd = PathBuf::from(format!("{home}/.config/fish/conf.d"));
-        fs::create_dir_all(&confd).ok();
-        let snip = confd.join("pkgbridge.fish");
-        let content = format!("# Added by pkgbridge: ensure user bin dir on PATH\nfish_add_path -g {}\n", bindir_str);
+        let snip = fish_snippet_path(&home);
+        if let Some(confd) = snip.parent() { dryrun::create_dir_all(confd).ok(); }
+        let content = format!("{}\nfish_add_path -g {}\n", FISH_HEADER, quote::fish(&bindir_str));
         // Write only if not already present or if content differs
         let write = match fs::read_to_string(&snip) { Ok(s) => s.trim() != content.trim(), Err(_) => true };
-        if write { fs::write(&snip, content).with_context(|| format!("writing {}", snip.display()))?; }
-        println!("Added fish PATH snippet: {} (restart fish or open a new terminal)", snip.display());
+        if write { dryrun::write(&snip, content).with_context(|| format!("writing {}", snip.display()))?; }
+        if !dryrun::enabled() { println!("Added fish PATH snippet: {} (restart fish or open a new terminal)", snip.display()); }
         return Ok(());
     }
     // For bash/zsh: append an idempotent block to ~/.profile
     let profile = PathBuf::from(format!("{home}/.profile"));
-    let marker = "# pkgbridge: add user bin to PATH";
-    let block = format!(
-        "{}\nif [ -d \"{}\" ] && ! printf %s \"\n:$PATH:\n\" | grep -q \"\n:{}:\n\"; then\n  export PATH=\"{}:$PATH\"\nfi\n",
-        marker, bindir_str, bindir_str, bindir_str
-    );
+    let marker = PROFILE_MARKER;
+    let block = format!("{}\n{}", marker, quote::sh_template(
+        "if [ -d {{dir}} ]; then\n  case \":$PATH:\" in\n    *:{{dir}}:*) ;;\n    *) export PATH={{dir}}:\"$PATH\";;\n  esac\nfi\n",
+        &[("dir", &bindir_str)],
+    ));
     let mut need_write = true;
     if let Ok(existing) = fs::read_to_string(&profile) {
         if existing.contains(&bindir_str) || existing.contains(marker) { need_write = false; }
     }
     if need_write {
-        let _ = fs::OpenOptions::new().create(true).append(true).open(&profile)
-            .and_then(|mut f| {
-                use std::io::Write as _;
-                writeln!(f, "\n{}", block)
-            });
-        println!("Ensured PATH in {} (restart your shell)", profile.display());
+        let _ = dryrun::append(&profile, format!("\n{}\n", block));
+        if !dryrun::enabled() { println!("Ensured PATH in {} (restart your shell)", profile.display()); }
     }
     Ok(())
 }
 
-fn default_box_for_family_key(fam_key: &str) -> (&'static str, &'static str) {
-    match fam_key {
-        "debian" | "ubuntu" => ("debian-stable", "docker.io/library/debian:stable"),
-        "fedora" => ("fedora-latest", "registry.fedoraproject.org/fedora:latest"),
-        "opensuse" => ("opensuse-tumbleweed", "registry.opensuse.org/opensuse/tumbleweed:latest"),
-        "arch" => ("arch", "docker.io/library/archlinux:latest"),
-        _ => ("distro", ""),
-    }
-}
-
-fn write_bootstrap_shim(dir: &PathBuf, wrapper_name: &str, fam_key: &str, mgr: &str) -> Result<()> {
-    let path = dir.join(wrapper_name);
-    let (def_name, def_img) = default_box_for_family_key(fam_key);
-    let content = format!("#!/usr/bin/env sh\nset -e\nfam=\"{}\"\nmgr=\"{}\"\ndef_name=\"{}\"\ndef_img=\"{}\"\n# If a default exists, use it; else offer to create one\nbox=$(pkgbridge pm show-defaults 2>/dev/null | awk -v f=\"$fam\" '$1==f && $2==\"=>\" {{print $3; exit}}')\nif [ -z \"$box\" ]; then\n  if [ -t 0 ]; then\n    echo \"pkgbridge: '$mgr' not found on host.\"\n    printf \"Create a %s box '%s' from '%s' and run '%s' from it? [Y/n] \" \"$fam\" \"$def_name\" \"$def_img\" \"$mgr\"\n    read ans || true\n    case \"$ans\" in \n      ''|y|Y|yes|YES)\n        distrobox create --name \"$def_name\" --image \"$def_img\" -Y --yes\n        pkgbridge pm set-default \"$fam\" \"$def_name\"\n        box=\"$def_name\"\n        ;;\n      *) echo \"Aborting.\"; exit 1;;\n    esac\n  else\n    echo \"pkgbridge: no default $fam container; run: pkgbridge pm set-default $fam $def_name; then pkgbridge pm generate-shims\"\n    exit 1\n  fi\nfi\n# Hand off to proper shim behavior (snapshot + sudo inside box)\npkgbridge pm snapshot --family \"$fam\" --container \"$box\" >/dev/null 2>&1 || true\nstatus=0\nif distrobox enter -n \"$box\" -- sh -lc 'command -v sudo >/dev/null' >/dev/null 2>&1; then\n  if distrobox enter -n \"$box\" -- sudo -n true >/dev/null 2>&1; then\n    distrobox enter -n \"$box\" -- sudo {} \"$@\" || status=$?\n  else\n    distrobox enter -n \"$box\" -- sudo {} \"$@\" || status=$?\n  fi\nelif distrobox enter -n \"$box\" -- sh -lc 'command -v doas >/dev/null' >/dev/null 2>&1; then\n  distrobox enter -n \"$box\" -- doas {} \"$@\" || status=$?\nelse\n  distrobox enter -n \"$box\" -- {} \"$@\" || status=$?\nfi\npkgbridge pm post-transaction --family \"$fam\" --container \"$box\" >/dev/null 2>&1 || true\nexit $status\n", fam_key, mgr, def_name, def_img, mgr, mgr, mgr, mgr);
-    fs::write(&path, content).with_context(|| format!("writing {}", path.display()))?;
-    let mut perms = fs::metadata(&path)?.permissions();
-    #[cfg(unix)] {
-        use std::os::unix::fs::PermissionsExt;
-        perms.set_mode(0o755);
-        fs::set_permissions(&path, perms)?;
-    }
-    Ok(())
-}
-
-fn generate_bootstrap_shims_into(bindir: &PathBuf) -> Result<()> {
-    // Create bootstrap shims only if the host doesn't already provide these managers
-    // Debian/Ubuntu
-    if !host_has_cmd_outside_bindir("apt", bindir) {
-        write_bootstrap_shim(bindir, "apt", "debian", "apt").ok();
-    }
-    if !host_has_cmd_outside_bindir("apt-get", bindir) {
-        write_bootstrap_shim(bindir, "apt-get", "debian", "apt-get").ok();
-    }
-    // Fedora
-    if !host_has_cmd_outside_bindir("dnf", bindir) {
-        write_bootstrap_shim(bindir, "dnf", "fedora", "dnf").ok();
-    }
-    // openSUSE
-    if !host_has_cmd_outside_bindir("zypper", bindir) {
-        write_bootstrap_shim(bindir, "zypper", "opensuse", "zypper").ok();
+/// Take out the PATH setup `ensure_bindir_on_path` added; returns the files it changed.
+pub fn undo_bindir_on_path() -> Result<Vec<PathBuf>> {
+    let home = std::env::var("HOME").unwrap_or_else(|_| ".".into());
+    let mut changed = Vec::new();
+    let profile = PathBuf::from(format!("{home}/.profile"));
+    if let Ok(existing) = fs::read_to_string(&profile) {
+        let mut lines: Vec<&str> = existing.lines().collect();
+        if let Some(start) = lines.iter().position(|l| *l == PROFILE_MARKER) {
+            // The block runs from the marker to its closing `fi`; the blank line before it was added too
+            let end = lines[start..].iter().position(|l| *l == "fi").map(|n| start + n + 1).unwrap_or(start + 1);
+            let from = if start > 0 && lines[start - 1].is_empty() { start - 1 } else { start };
+            let end = if end + 1 == lines.len() && lines[end].is_empty() { end + 1 } else { end };
+            lines.drain(from..end);
+            let mut content = lines.join("\n");
+            if !content.is_empty() { content.push('\n'); }
+            dryrun::write(&profile, content).with_context(|| format!("writing {}", profile.display()))?;
+            changed.push(profile);
+        }
     }
-    // Arch
-    if !host_has_cmd_outside_bindir("pacman", bindir) {
-        write_bootstrap_shim(bindir, "pacman", "arch", "pacman").ok();
+    let snip = fish_snippet_path(&home);
+    if fs::read_to_string(&snip).map(|s| s.starts_with(FISH_HEADER)).unwrap_or(false) {
+        dryrun::remove_file(&snip).with_context(|| format!("removing {}", snip.display()))?;
+        changed.push(snip);
     }
-    Ok(())
-}
-
-pub fn generate_bootstrap_shims() -> Result<()> {
-    let home = std::env::var("HOME").unwrap_or_else(|_| ".".into());
-    let bindir: PathBuf = std::env::var("XDG_BIN_HOME").map(PathBuf::from).unwrap_or_else(|_| PathBuf::from(format!("{home}/.local/bin")));
-    fs::create_dir_all(&bindir).ok();
-    generate_bootstrap_shims_into(&bindir)?;
-    ensure_bindir_on_path(&bindir)?;
-    Ok(())
+    Ok(changed)
 }
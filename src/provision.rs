@@ -0,0 +1,146 @@
+use crate::config::{self, AurHelper, ProvisionAction, ProvisionStep};
+use crate::distro::{self, Family};
+use crate::{pkgops, pm, quote};
+use anyhow::{anyhow, Context, Result};
+use std::path::PathBuf;
+
+/// Image configured for the family's box template, if any.
+pub fn template_image(fam: Family) -> Result<Option<String>> {
+    Ok(config::load_config()?.templates.get(pm::family_key(fam)).and_then(|t| t.image.clone()))
+}
+
+/// Create a box and run the family template's provisioning steps inside it.
+pub fn create_and_provision(name: &str, image: &str, fam: Family) -> Result<()> {
+    distro::create_box(name, image)?;
+    provision_box(name, fam)
+}
+
+/// Apply every provisioning step of the family template that has not yet run in this box.
+/// Steps run as root through the box user's sudo/doas; each one is recorded in state after it succeeds so it never runs twice.
+/// Arch boxes then get the configured AUR helper.
+pub fn provision_box(name: &str, fam: Family) -> Result<()> {
+    let cfg = config::load_config()?;
+    if cfg.nopasswd_pm { allow_pm_without_password(name, fam)?; }
+    let steps = cfg.templates.get(pm::family_key(fam)).map(|t| t.provision.clone()).unwrap_or_default();
+    let mut st = config::load_state()?;
+    for step in &steps {
+        let id = step_id(step);
+        if st.provisioned.get(name).map(|d| d.contains(&id)).unwrap_or(false) {
+            log::debug!("provisioning step '{}' already applied in {}", id, name);
+            continue;
+        }
+        println!("Provisioning '{}': {}", name, id);
+        apply_step(name, fam, &step.action).with_context(|| format!("provisioning step '{}' in {}", id, name))?;
+        st.provisioned.entry(name.to_string()).or_default().push(id);
+        config::save_state(&st)?;
+    }
+    if let (Family::Arch, Some(helper)) = (fam, cfg.aur_helper) {
+        let id = format!("aur-helper:{}", helper.name());
+        if !st.provisioned.get(name).map(|d| d.contains(&id)).unwrap_or(false) {
+            install_aur_helper(name, helper)?;
+            st.provisioned.entry(name.to_string()).or_default().push(id);
+            config::save_state(&st)?;
+        }
+    }
+    Ok(())
+}
+
+/// Build an AUR helper from its `-bin` AUR package inside an Arch box. makepkg refuses to run as
+/// root, so this runs as the user and elevates with sudo (prompts reach the host terminal).
+pub fn install_aur_helper(name: &str, helper: AurHelper) -> Result<()> {
+    println!("Building {} from the AUR in '{}'...", helper.name(), name);
+    let cmd = quote::sh_template(r#"set -e
+command -v {{helper}} >/dev/null && exit 0
+s=; if command -v sudo >/dev/null; then s=sudo; elif command -v doas >/dev/null; then s=doas; fi
+$s pacman -S --needed --noconfirm git base-devel
+d=$(mktemp -d); trap 'rm -rf "$d"' EXIT
+git clone --depth 1 {{url}} "$d/src"
+cd "$d/src" && makepkg -si --noconfirm
+"#, &[("helper", helper.name()), ("url", &format!("https://aur.archlinux.org/{}-bin.git", helper.name()))]);
+    if !distro::enter_status_inherit(name, &cmd, false)? {
+        return Err(anyhow!("building {} in {} failed", helper.name(), name));
+    }
+    Ok(())
+}
+
+fn apply_step(name: &str, fam: Family, action: &ProvisionAction) -> Result<()> {
+    let ok = match action {
+        ProvisionAction::Run { script } => distro::enter_status_elevated(name, script)?,
+        ProvisionAction::Copy { src, dest, mode } => {
+            let src = expand_home(src);
+            let staged = distro::copy_into_box(name, &src)?;
+            let mode = mode.clone().unwrap_or_else(|| "0644".into());
+            let cmd = quote::sh_template(
+                "set -e; install -D -m {{mode}} {{src}} {{dest}}; rm -f {{src}}",
+                &[("mode", &mode), ("src", &staged), ("dest", dest)],
+            );
+            distro::enter_status_elevated(name, &cmd)?
+        }
+        ProvisionAction::Packages { names } => {
+            if names.is_empty() { return Ok(()); }
+            distro::enter_status_elevated(name, &pkgops::install_names_cmd(fam, names))?
+        }
+    };
+    if !ok { return Err(anyhow!("command failed inside {}", name)); }
+    Ok(())
+}
+
+/// sudoers drop-in written by `box nopasswd`.
+const SUDOERS_DROPIN: &str = "/etc/sudoers.d/pkgbridge-pm";
+
+/// Install a sudoers drop-in letting the host user run the family's package managers without a password.
+pub fn allow_pm_without_password(name: &str, fam: Family) -> Result<()> {
+    let user = std::env::var("USER").unwrap_or_default();
+    // Distrobox mirrors the host user; refuse anything that is not a plain user name
+    if user.is_empty() || !user.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.') {
+        return Err(anyhow!("cannot write a sudoers rule for user '{}'", user));
+    }
+    let rule = format!(
+        "# Written by pkgbridge: package managers run without a password\n{} ALL=(root) NOPASSWD: {}\n",
+        user,
+        pkgops::pm_binaries(fam).join(", ")
+    );
+    // The rule arrives on stdin; it is checked with visudo (when present) before it goes live
+    let cmd = format!(
+        "set -e; mkdir -p /etc/sudoers.d; t={f}.new; cat > \"$t\"; chmod 0440 \"$t\"; \
+         if command -v visudo >/dev/null 2>&1 && ! visudo -cf \"$t\" >/dev/null; then rm -f \"$t\"; exit 1; fi; mv -f \"$t\" {f}",
+        f = SUDOERS_DROPIN
+    );
+    if !distro::enter_status_elevated_with_input(name, &cmd, rule.as_bytes())? {
+        return Err(anyhow!("writing {} inside {} failed", SUDOERS_DROPIN, name));
+    }
+    Ok(())
+}
+
+/// Remove the drop-in written by [`allow_pm_without_password`].
+pub fn revoke_pm_without_password(name: &str) -> Result<()> {
+    if !distro::enter_status_elevated(name, &format!("rm -f {}", SUDOERS_DROPIN))? {
+        return Err(anyhow!("removing {} inside {} failed", SUDOERS_DROPIN, name));
+    }
+    Ok(())
+}
+
+/// Explicit step id, or a fingerprint of the step so edited steps run again.
+fn step_id(step: &ProvisionStep) -> String {
+    if let Some(id) = &step.id { return id.clone(); }
+    let kind = match step.action {
+        ProvisionAction::Run { .. } => "run",
+        ProvisionAction::Copy { .. } => "copy",
+        ProvisionAction::Packages { .. } => "packages",
+    };
+    let repr = serde_json::to_string(&step.action).unwrap_or_default();
+    // FNV-1a: stable across builds, unlike std's DefaultHasher
+    let mut h: u64 = 0xcbf29ce484222325;
+    for b in repr.bytes() {
+        h ^= b as u64;
+        h = h.wrapping_mul(0x100000001b3);
+    }
+    format!("{}-{:016x}", kind, h)
+}
+
+fn expand_home(p: &std::path::Path) -> PathBuf {
+    match p.to_str().and_then(|s| s.strip_prefix("~/")) {
+        Some(rest) => PathBuf::from(std::env::var("HOME").unwrap_or_else(|_| ".".into())).join(rest),
+        None => p.to_path_buf(),
+    }
+}
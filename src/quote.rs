@@ -0,0 +1,189 @@
+/// Characters that never need quoting in POSIX sh words.
+fn sh_safe(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | '/' | ',' | ':' | '@' | '%' | '+' | '=')
+}
+
+/// Quote `s` as a single POSIX sh word. Safe words are left bare to keep scripts readable.
+pub fn sh(s: &str) -> String {
+    if !s.is_empty() && s.chars().all(sh_safe) {
+        return s.to_string();
+    }
+    // Inside single quotes nothing is special except the quote itself: close, escape, reopen
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+/// Quote `s` as a single fish word.
+pub fn fish(s: &str) -> String {
+    if !s.is_empty() && s.chars().all(sh_safe) {
+        return s.to_string();
+    }
+    format!("'{}'", s.replace('\\', r"\\").replace('\'', r"\'"))
+}
+
+/// Render a shell script template, replacing every `{{name}}` with the sh-quoted value of `name`.
+/// Template text outside placeholders is copied verbatim, so `$var`, `"$@"` and awk braces are safe.
+pub fn sh_template(template: &str, vars: &[(&str, &str)]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("}}") else {
+            out.push_str(&rest[start..]);
+            return out;
+        };
+        let key = &after[..end];
+        match vars.iter().find(|(k, _)| *k == key) {
+            Some((_, v)) => out.push_str(&sh(v)),
+            None => panic!("template variable '{}' has no value", key),
+        }
+        rest = &after[end + 2..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Quote `s` as one argument of a desktop entry `Exec` key (quoting rules of the
+/// Desktop Entry spec, then escaped again as a string value).
+pub fn desktop_exec_arg(s: &str) -> String {
+    let reserved = |c: char| c.is_whitespace() || "\"'\\><~|&;$*?#()`".contains(c);
+    let arg = if !s.is_empty() && !s.chars().any(reserved) {
+        s.to_string()
+    } else {
+        let mut q = String::from("\"");
+        for c in s.chars() {
+            if matches!(c, '"' | '`' | '$' | '\\') { q.push('\\'); }
+            q.push(c);
+        }
+        q.push('"');
+        q
+    };
+    // Literal percent signs would be read as field codes; backslashes (and line breaks) are
+    // escaped once more because the whole Exec value is itself a desktop-entry string
+    arg.replace('%', "%%").replace('\\', r"\\").replace('\n', r"\n").replace('\r', r"\r")
+}
+
+/// Build an `Exec` value from literal arguments.
+pub fn desktop_exec(args: &[&str]) -> String {
+    args.iter().map(|a| desktop_exec_arg(a)).collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOSTILE: &[&str] = &[
+        "",
+        "plain",
+        "it's",
+        "'",
+        "''",
+        "say \"hi\"",
+        "$(touch /tmp/pwned)",
+        "`id`",
+        "$HOME",
+        "100%",
+        "%f",
+        "two words",
+        "line\nbreak",
+        "-rf",
+        "--help",
+        "back\\slash",
+        "tab\there",
+        "semi;colon && pipe|",
+        "*?[glob]",
+        "~user",
+    ];
+
+    /// What `sh` makes of a quoted word.
+    fn through_sh(word: &str) -> String {
+        let out = std::process::Command::new("sh").arg("-c").arg(format!("printf '%s' {}", word)).output().unwrap();
+        assert!(out.status.success(), "sh rejected {:?}", word);
+        String::from_utf8(out.stdout).unwrap()
+    }
+
+    #[test]
+    fn sh_leaves_safe_words_bare() {
+        assert_eq!(sh("apt-get"), "apt-get");
+        assert_eq!(sh("/usr/bin/foo_1.2+x"), "/usr/bin/foo_1.2+x");
+        assert_eq!(sh("-rf"), "-rf");
+        assert_eq!(sh("100%"), "100%");
+    }
+
+    #[test]
+    fn sh_quotes_everything_else() {
+        assert_eq!(sh(""), "''");
+        assert_eq!(sh("two words"), "'two words'");
+        assert_eq!(sh("it's"), r"'it'\''s'");
+        assert_eq!(sh("$(id)"), "'$(id)'");
+        assert_eq!(sh("`id`"), "'`id`'");
+    }
+
+    #[test]
+    fn sh_round_trips_through_a_real_shell() {
+        for s in HOSTILE {
+            assert_eq!(&through_sh(&sh(s)), s, "quoted as {}", sh(s));
+        }
+    }
+
+    #[test]
+    fn sh_template_quotes_values_and_keeps_the_template_verbatim() {
+        let out = sh_template(r#"echo "$HOME" {{a}} {{b}}; awk '{print $1}'"#, &[("a", "x y"), ("b", "$(id)")]);
+        assert_eq!(out, r#"echo "$HOME" 'x y' '$(id)'; awk '{print $1}'"#);
+    }
+
+    #[test]
+    fn sh_template_does_not_expand_placeholders_inside_values() {
+        assert_eq!(sh_template("{{a}} {{b}}", &[("a", "{{b}}"), ("b", "it's")]), r"'{{b}}' 'it'\''s'");
+    }
+
+    #[test]
+    fn sh_template_leaves_an_unclosed_placeholder_alone() {
+        assert_eq!(sh_template("echo {{a", &[("a", "x")]), "echo {{a");
+    }
+
+    #[test]
+    fn sh_template_values_round_trip_through_a_real_shell() {
+        for s in HOSTILE {
+            assert_eq!(&through_sh(&sh_template("{{v}}", &[("v", s)])), s);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "template variable 'missing' has no value")]
+    fn sh_template_panics_on_unknown_placeholder() {
+        sh_template("echo {{missing}}", &[("a", "x")]);
+    }
+
+    #[test]
+    fn desktop_exec_arg_leaves_plain_words_bare() {
+        assert_eq!(desktop_exec_arg("firefox"), "firefox");
+        assert_eq!(desktop_exec_arg("-new-window"), "-new-window");
+        assert_eq!(desktop_exec_arg("/opt/app/bin"), "/opt/app/bin");
+    }
+
+    #[test]
+    fn desktop_exec_arg_quotes_reserved_characters() {
+        assert_eq!(desktop_exec_arg(""), r#""""#);
+        assert_eq!(desktop_exec_arg("two words"), r#""two words""#);
+        assert_eq!(desktop_exec_arg("it's"), r#""it's""#);
+        // Inside quotes ", `, $ and \ get a backslash, which is then doubled for the string value
+        assert_eq!(desktop_exec_arg(r#"say "hi""#), r#""say \\"hi\\"""#);
+        assert_eq!(desktop_exec_arg("$(id)"), r#""\\$(id)""#);
+        assert_eq!(desktop_exec_arg("`id`"), r#""\\`id\\`""#);
+        assert_eq!(desktop_exec_arg(r"back\slash"), r#""back\\\\slash""#);
+    }
+
+    #[test]
+    fn desktop_exec_arg_escapes_percent_and_line_breaks() {
+        assert_eq!(desktop_exec_arg("100%"), "100%%");
+        assert_eq!(desktop_exec_arg("%f"), "%%f");
+        assert_eq!(desktop_exec_arg("line\nbreak"), r#""line\nbreak""#);
+        assert_eq!(desktop_exec_arg("cr\rhere"), r#""cr\rhere""#);
+    }
+
+    #[test]
+    fn desktop_exec_joins_arguments() {
+        assert_eq!(desktop_exec(&["distrobox", "enter", "-n", "my box", "--", "app", "%U"]), r#"distrobox enter -n "my box" -- app %%U"#);
+    }
+}
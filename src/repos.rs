@@ -0,0 +1,289 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+
+use crate::config::{self, RepoRecord};
+use crate::distro::{self, Family};
+use crate::quote;
+
+/// What a repository spec refers to.
+enum Source<'a> {
+    /// `ppa:user/name` (Ubuntu)
+    Ppa(&'a str),
+    /// `copr:owner/project` (Fedora)
+    Copr(&'a str),
+    /// `obs:project[/platform]` (openSUSE Build Service)
+    Obs { project: &'a str, platform: Option<&'a str> },
+    /// A repository URL, a `.repo` file URL or (Debian) a one-line `deb ...` entry
+    Url(&'a str),
+}
+
+fn parse(spec: &str) -> Source<'_> {
+    if let Some(p) = spec.strip_prefix("ppa:") { return Source::Ppa(p); }
+    if let Some(p) = spec.strip_prefix("copr:") { return Source::Copr(p); }
+    if let Some(p) = spec.strip_prefix("obs:") {
+        return match p.split_once('/') {
+            Some((project, platform)) => Source::Obs { project, platform: Some(platform) },
+            None => Source::Obs { project: p, platform: None },
+        };
+    }
+    Source::Url(spec)
+}
+
+/// Lowercase `s` and keep it to `[a-z0-9-]`, usable in file names and repo aliases.
+fn slug(s: &str) -> String {
+    let mut out = String::new();
+    for c in s.chars() {
+        let c = c.to_ascii_lowercase();
+        if c.is_ascii_alphanumeric() { out.push(c); } else if !out.ends_with('-') && !out.is_empty() { out.push('-'); }
+    }
+    out.trim_end_matches('-').to_string()
+}
+
+/// Name recorded for a spec when `--name` is not given.
+fn default_name(spec: &str) -> String {
+    match parse(spec) {
+        Source::Ppa(p) => format!("ppa-{}", slug(p)),
+        Source::Copr(p) => format!("copr-{}", slug(p)),
+        Source::Obs { project, .. } => format!("obs-{}", slug(project)),
+        Source::Url(u) => {
+            // Host and path of the URL, whatever surrounds it in a deb line
+            let url = u.split_whitespace().find(|w| w.contains("://")).unwrap_or(u);
+            slug(url.split_once("://").map(|(_, rest)| rest).unwrap_or(url))
+        }
+    }
+}
+
+/// Repositories pkgbridge added to a box.
+pub fn list(box_name: &str) -> Result<Vec<RepoRecord>> {
+    Ok(config::load_state()?.repos.get(box_name).cloned().unwrap_or_default())
+}
+
+/// Add a repository to a box and record it so `box rebuild` can add it again.
+pub fn add(box_name: &str, fam: Family, spec: &str, key: Option<&str>, name: Option<&str>) -> Result<RepoRecord> {
+    let name = match name {
+        Some(n) if slug(n) == n && !n.is_empty() => n.to_string(),
+        Some(n) => return Err(anyhow!("repository name '{}' may only contain a-z, 0-9 and '-'", n)),
+        None if fam == Family::Arch => {
+            return Err(anyhow!("pacman repositories need --name matching the repository's database name"));
+        }
+        None => default_name(spec),
+    };
+    // Only pacman-key can fetch a key by fingerprint; the other families need the key itself
+    if let Some(k) = key.filter(|k| is_fingerprint(k) && !Path::new(k).is_file()) {
+        if fam != Family::Arch {
+            return Err(anyhow!("--key {} looks like a fingerprint, which only Arch boxes can fetch; pass a key file or URL", k));
+        }
+    }
+    // Host key files are recorded by absolute path so a rebuild finds them from any directory
+    let key = key.map(|k| match Path::new(k).canonicalize() {
+        Ok(p) if !k.contains("://") => p.to_string_lossy().to_string(),
+        _ => k.to_string(),
+    });
+    let rec = RepoRecord { name, spec: spec.to_string(), key };
+    apply(box_name, fam, &rec)?;
+    let mut st = config::load_state()?;
+    let recs = st.repos.entry(box_name.to_string()).or_default();
+    recs.retain(|r| r.name != rec.name);
+    recs.push(rec.clone());
+    config::save_state(&st)?;
+    Ok(rec)
+}
+
+/// Configure a recorded repository inside a box (again).
+pub fn apply(box_name: &str, fam: Family, rec: &RepoRecord) -> Result<()> {
+    // Keys on the host are staged into the box; URLs are fetched from inside it
+    let staged = match rec.key.as_deref() {
+        Some(k) if !k.contains("://") && Path::new(k).is_file() => Some(distro::copy_into_box(box_name, Path::new(k))?),
+        Some(k) if !k.contains("://") && !is_fingerprint(k) => return Err(anyhow!("key file {} not found", k)),
+        _ => None,
+    };
+    let key = staged.as_deref().or(rec.key.as_deref());
+    let mut cmd = add_cmd(fam, rec, key)?;
+    if let Some(s) = &staged { cmd.push_str(&format!("\nrm -f {}", quote::sh(s))); }
+    if !distro::enter_status_elevated(box_name, &cmd)? {
+        return Err(anyhow!("adding repository '{}' failed inside {}", rec.name, box_name));
+    }
+    Ok(())
+}
+
+/// Remove a repository added by `repo add`, by name or spec.
+pub fn remove(box_name: &str, fam: Family, repo: &str) -> Result<RepoRecord> {
+    let rec = list(box_name)?
+        .into_iter()
+        .find(|r| r.name == repo || r.spec == repo)
+        .ok_or_else(|| anyhow!("'{}' was not added to '{}' by pkgbridge (see: pkgbridge repo list)", repo, box_name))?;
+    let cmd = rm_cmd(fam, &rec)?;
+    if !distro::enter_status_elevated(box_name, &cmd)? {
+        return Err(anyhow!("removing repository '{}' failed inside {}", rec.name, box_name));
+    }
+    let mut st = config::load_state()?;
+    if let Some(recs) = st.repos.get_mut(box_name) { recs.retain(|r| r.name != rec.name); }
+    st.repos.retain(|_, recs| !recs.is_empty());
+    config::save_state(&st)?;
+    Ok(rec)
+}
+
+fn is_fingerprint(k: &str) -> bool {
+    k.len() == 40 && k.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Shell function `fetch <src> <dest>`: download URLs, copy files staged in the box.
+const FETCH: &str = r#"fetch() { case "$1" in
+  *://*) if command -v curl >/dev/null; then curl -fsSL "$1" -o "$2"; else wget -qO "$2" "$1"; fi;;
+  *) cp "$1" "$2";;
+esac; }
+"#;
+
+fn wrong_family(spec: &str, fam: Family) -> anyhow::Error {
+    anyhow!("'{}' cannot be added to a {} box", spec, crate::pm::family_key(fam))
+}
+
+/// Root script adding `rec` in a box of family `fam`; `key` is where the key can be read inside the box.
+fn add_cmd(fam: Family, rec: &RepoRecord, key: Option<&str>) -> Result<String> {
+    let name = rec.name.as_str();
+    let key = key.unwrap_or("");
+    let script = match (fam, parse(&rec.spec)) {
+        (Family::Debian, Source::Ppa(_)) => quote::sh_template(r#"set -e; export DEBIAN_FRONTEND=noninteractive
+command -v add-apt-repository >/dev/null || { apt-get -y update; apt-get -y install software-properties-common; }
+add-apt-repository -y {{spec}}
+apt-get -y update
+"#, &[("spec", &rec.spec)]),
+        (Family::Debian, Source::Url(line)) => {
+            let entry = line.trim().strip_prefix("deb ").unwrap_or(line.trim()).trim_start();
+            let (opts, rest) = match entry.strip_prefix('[').and_then(|e| e.split_once(']')) {
+                Some((o, r)) => (o.trim().to_string(), r.trim().to_string()),
+                None => (String::new(), entry.to_string()),
+            };
+            if rest.split_whitespace().count() < 2 {
+                return Err(anyhow!("expected 'deb [options] <url> <suite> [components...]', got '{}'", line));
+            }
+            // signed-by points at the keyring written below; apt reads armored keys from .asc files
+            let head = if opts.is_empty() { "deb [signed-by=".to_string() } else { format!("deb [{} signed-by=", opts) };
+            let plain = if opts.is_empty() { format!("deb {}", rest) } else { format!("deb [{}] {}", opts, rest) };
+            let tail = format!("] {}", rest);
+            quote::sh_template(&format!("{}{}", FETCH, r#"set -e
+list=/etc/apt/sources.list.d/pkgbridge-{{name}}.list
+if [ -n {{key}} ]; then
+  mkdir -p /etc/apt/keyrings
+  kr=/etc/apt/keyrings/pkgbridge-{{name}}.gpg
+  fetch {{key}} "$kr.tmp"
+  if grep -q 'BEGIN PGP' "$kr.tmp"; then kr=/etc/apt/keyrings/pkgbridge-{{name}}.asc; fi
+  mv "$kr.tmp" "$kr"; chmod 644 "$kr"
+  printf '%s%s%s\n' {{head}} "$kr" {{tail}} > "$list"
+else
+  printf '%s\n' {{plain}} > "$list"
+fi
+apt-get -y update
+"#), &[("name", name), ("key", key), ("head", &head), ("tail", &tail), ("plain", &plain)])
+        }
+        (Family::Fedora, Source::Copr(project)) => quote::sh_template(r#"set -e
+dnf -y copr enable {{project}} || { dnf -y install 'dnf-command(copr)'; dnf -y copr enable {{project}}; }
+"#, &[("project", project)]),
+        (Family::Fedora | Family::OpenSuse, Source::Url(url)) => {
+            let dir = if fam == Family::Fedora { "/etc/yum.repos.d" } else { "/etc/zypp/repos.d" };
+            let refresh = if fam == Family::Fedora { "dnf -y makecache --repo pkgbridge-{{name}} || dnf -y makecache" } else { "zypper --non-interactive refresh" };
+            let body = format!(r#"{fetch}set -e
+repo={dir}/pkgbridge-{{{{name}}}}.repo
+if [ -n {{{{key}}}} ]; then
+  mkdir -p /etc/pki/rpm-gpg
+  fetch {{{{key}}}} /etc/pki/rpm-gpg/pkgbridge-{{{{name}}}}.asc
+  rpm --import /etc/pki/rpm-gpg/pkgbridge-{{{{name}}}}.asc
+  check=1
+else
+  check=0
+fi
+case {{{{url}}}} in
+  *.repo) fetch {{{{url}}}} "$repo";;
+  *) printf '[pkgbridge-%s]\nname=%s (pkgbridge)\nbaseurl=%s\nenabled=1\ngpgcheck=%s\n' {{{{name}}}} {{{{name}}}} {{{{url}}}} "$check" > "$repo"
+     if [ "$check" = 1 ]; then echo "gpgkey=file:///etc/pki/rpm-gpg/pkgbridge-{{{{name}}}}.asc" >> "$repo"; fi;;
+esac
+{refresh}
+"#, fetch = FETCH, dir = dir, refresh = refresh);
+            quote::sh_template(&body, &[("name", name), ("key", key), ("url", url)])
+        }
+        (Family::OpenSuse, Source::Obs { project, platform }) => {
+            // OBS publishes home:user:proj under home:/user:/proj/<platform>/
+            let path = project.replace(':', ":/");
+            quote::sh_template(r#"set -e
+p={{platform}}
+if [ -z "$p" ]; then
+  . /etc/os-release
+  case "$ID" in
+    opensuse-tumbleweed) p=openSUSE_Tumbleweed;;
+    opensuse-slowroll) p=openSUSE_Slowroll;;
+    *) p=$VERSION_ID;;
+  esac
+fi
+zypper --non-interactive addrepo -f "https://download.opensuse.org/repositories/"{{path}}"/$p/" pkgbridge-{{name}}
+zypper --non-interactive --gpg-auto-import-keys refresh pkgbridge-{{name}}
+"#, &[("platform", platform.unwrap_or("")), ("path", &path), ("name", name)])
+        }
+        (Family::Arch, Source::Url(url)) => {
+            let key_step = if is_fingerprint(key) {
+                "pacman-key --recv-keys {{key}}\n  pacman-key --lsign-key {{key}}"
+            } else {
+                r#"t=$(mktemp); fetch {{key}} "$t"
+  pacman-key --add "$t"
+  for f in $(gpg --with-colons --import-options show-only --import "$t" 2>/dev/null | awk -F: '$1 == "fpr" { print $10 }'); do pacman-key --lsign-key "$f"; done
+  rm -f "$t""#
+            };
+            let body = format!(r#"{fetch}set -e
+if [ -n {{{{key}}}} ]; then
+  {key_step}
+fi
+{drop}
+printf '\n# pkgbridge repo: %s\n[%s]\nServer = %s\n# end pkgbridge repo: %s\n' {{{{name}}}} {{{{name}}}} {{{{url}}}} {{{{name}}}} >> /etc/pacman.conf
+pacman -Sy --noconfirm
+"#, fetch = FETCH, key_step = key_step, drop = PACMAN_DROP_BLOCK);
+            quote::sh_template(&body, &[("name", name), ("key", key), ("url", url)])
+        }
+        (Family::Alpine, Source::Url(url)) => {
+            // apk matches keys by file name, so keep the key's own
+            let key_file = rec.key.as_deref().and_then(|k| k.rsplit('/').next()).unwrap_or("");
+            quote::sh_template(&format!("{}{}", FETCH, r#"set -e
+if [ -n {{key}} ]; then fetch {{key}} /etc/apk/keys/{{key_file}}; fi
+grep -qxF {{url}} /etc/apk/repositories || echo {{url}} >> /etc/apk/repositories
+apk update
+"#), &[("key", key), ("key_file", key_file), ("url", url)])
+        }
+        (Family::Void, Source::Url(url)) => quote::sh_template(r#"set -e
+mkdir -p /etc/xbps.d
+echo "repository="{{url}} > /etc/xbps.d/pkgbridge-{{name}}.conf
+xbps-install -Sy
+"#, &[("url", url), ("name", name)]),
+        (Family::Gentoo, _) => return Err(anyhow!("repo add is not supported for gentoo boxes; use 'eselect repository' inside the box")),
+        _ => return Err(wrong_family(&rec.spec, fam)),
+    };
+    Ok(script)
+}
+
+/// Deletes the marked block for `{{name}}` from pacman.conf.
+const PACMAN_DROP_BLOCK: &str = r##"awk -v b="# pkgbridge repo: "{{name}} -v e="# end pkgbridge repo: "{{name}} '$0 == b { skip = 1 } !skip { print } $0 == e { skip = 0 }' /etc/pacman.conf > /etc/pacman.conf.pkgbridge && cat /etc/pacman.conf.pkgbridge > /etc/pacman.conf && rm -f /etc/pacman.conf.pkgbridge"##;
+
+/// Root script undoing [`add_cmd`].
+fn rm_cmd(fam: Family, rec: &RepoRecord) -> Result<String> {
+    let name = rec.name.as_str();
+    let script = match (fam, parse(&rec.spec)) {
+        (Family::Debian, Source::Ppa(_)) => quote::sh_template("set -e; add-apt-repository -y --remove {{spec}}; apt-get -y update\n", &[("spec", &rec.spec)]),
+        (Family::Debian, Source::Url(_)) => quote::sh_template(
+            "rm -f /etc/apt/sources.list.d/pkgbridge-{{name}}.list /etc/apt/keyrings/pkgbridge-{{name}}.gpg /etc/apt/keyrings/pkgbridge-{{name}}.asc; apt-get -y update\n",
+            &[("name", name)],
+        ),
+        (Family::Fedora, Source::Copr(project)) => quote::sh_template("dnf -y copr remove {{project}}\n", &[("project", project)]),
+        // Imported rpm keys stay trusted; only the repository goes away
+        (Family::Fedora, Source::Url(_)) => quote::sh_template("rm -f /etc/yum.repos.d/pkgbridge-{{name}}.repo /etc/pki/rpm-gpg/pkgbridge-{{name}}.asc\n", &[("name", name)]),
+        (Family::OpenSuse, Source::Url(_) | Source::Obs { .. }) => quote::sh_template(
+            "zypper --non-interactive removerepo pkgbridge-{{name}} 2>/dev/null || true; rm -f /etc/zypp/repos.d/pkgbridge-{{name}}.repo /etc/pki/rpm-gpg/pkgbridge-{{name}}.asc\n",
+            &[("name", name)],
+        ),
+        (Family::Arch, Source::Url(_)) => quote::sh_template(&format!("set -e\n{}\npacman -Sy --noconfirm\n", PACMAN_DROP_BLOCK), &[("name", name)]),
+        (Family::Alpine, Source::Url(url)) => quote::sh_template(
+            "set -e; grep -vxF {{url}} /etc/apk/repositories > /etc/apk/repositories.pkgbridge || true; cat /etc/apk/repositories.pkgbridge > /etc/apk/repositories; rm -f /etc/apk/repositories.pkgbridge\n",
+            &[("url", url)],
+        ),
+        (Family::Void, Source::Url(_)) => quote::sh_template("rm -f /etc/xbps.d/pkgbridge-{{name}}.conf\n", &[("name", name)]),
+        _ => return Err(wrong_family(&rec.spec, fam)),
+    };
+    Ok(script)
+}
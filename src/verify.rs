@@ -0,0 +1,291 @@
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use anyhow::{anyhow, Context, Result};
+
+use crate::config;
+use crate::dryrun;
+use crate::pkgdetect::PackageFormat;
+use crate::pkgmeta;
+
+/// Signature tags in the rpm signature header that sign the main header.
+const RPMSIGTAG_DSAHEADER: u32 = 267;
+const RPMSIGTAG_RSAHEADER: u32 = 268;
+const RPMSIGTAG_OPENPGP: u32 = 278;
+/// Largest debsigs signature member read into memory; real ones are a few KiB.
+const MAX_DEB_SIG_SIZE: u64 = 1 << 20;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SigStatus {
+    /// Good signature from a trusted key
+    Good { fingerprint: String, method: &'static str },
+    /// Signed, but not by any key in the pkgbridge keyring
+    UnknownKey { key_id: String, method: &'static str },
+    /// Signature present but does not match the data
+    Bad { method: &'static str },
+    /// Cryptographically valid but not acceptable: expired or revoked key, expired signature,
+    /// or signed data that does not cover the whole package
+    Rejected { reason: &'static str, method: &'static str },
+    /// No signature found
+    Unsigned,
+}
+
+/// Verify a package against the trusted keys: a detached `<file>.asc`/`<file>.sig` first,
+/// then the embedded signature (rpm header signature, debsigs `_gpg*` member).
+pub fn verify_package(path: &Path, fmt: PackageFormat) -> Result<SigStatus> {
+    for ext in ["asc", "sig"] {
+        let detached = PathBuf::from(format!("{}.{}", path.display(), ext));
+        if detached.exists() {
+            let data = File::open(path).with_context(|| format!("opening {}", path.display()))?;
+            return gpgv(&detached, Box::new(data), "detached signature");
+        }
+    }
+    match fmt {
+        PackageFormat::Rpm => verify_rpm(path),
+        PackageFormat::Deb => verify_deb(path),
+    }
+}
+
+fn verify_rpm(path: &Path) -> Result<SigStatus> {
+    let (sig, main) = pkgmeta::read_rpm_headers(path)?;
+    let Some(packet) = [RPMSIGTAG_OPENPGP, RPMSIGTAG_RSAHEADER, RPMSIGTAG_DSAHEADER].iter().find_map(|t| sig.bin(*t)) else {
+        return Ok(SigStatus::Unsigned);
+    };
+    let sig_file = TempFile::write("rpm-header.sig", &packet)?;
+    let method = "rpm header signature";
+    let status = gpgv(&sig_file.0, Box::new(std::io::Cursor::new(main.raw.clone())), method)?;
+    if !matches!(status, SigStatus::Good { .. }) { return Ok(status); }
+    // The header signature covers the payload only through the payload digest stored in the header
+    let Some(expected) = main.string(pkgmeta::RPMTAG_PAYLOADDIGEST) else {
+        return Ok(SigStatus::Rejected { reason: "no payload digest, so the signature does not cover the payload", method });
+    };
+    let algo = main.int32s(pkgmeta::RPMTAG_PAYLOADDIGESTALGO).first().copied().unwrap_or(8);
+    let mut f = File::open(path)?;
+    f.seek(SeekFrom::Start(pkgmeta::rpm_payload_offset(&sig, &main)))?;
+    let actual = match algo {
+        8 => hex_digest::<sha2::Sha256>(&mut f)?,
+        9 => hex_digest::<sha2::Sha384>(&mut f)?,
+        10 => hex_digest::<sha2::Sha512>(&mut f)?,
+        _ => return Ok(SigStatus::Rejected { reason: "unsupported payload digest algorithm", method }),
+    };
+    if !actual.eq_ignore_ascii_case(expected.trim()) { return Ok(SigStatus::Bad { method: "rpm payload digest" }); }
+    Ok(status)
+}
+
+fn verify_deb(path: &Path) -> Result<SigStatus> {
+    let mut f = File::open(path)?;
+    let members = pkgmeta::ar_members(&mut f)?;
+    let Some(sig_member) = members.iter().find(|m| m.name.starts_with("_gpg")) else {
+        return Ok(SigStatus::Unsigned);
+    };
+    if sig_member.size > MAX_DEB_SIG_SIZE {
+        return Ok(SigStatus::Rejected { reason: "the signature member is implausibly large", method: "debsigs signature" });
+    }
+    let mut packet = vec![0u8; sig_member.size as usize];
+    f.seek(SeekFrom::Start(sig_member.offset))?;
+    f.read_exact(&mut packet)?;
+    let sig_file = TempFile::write("deb-origin.sig", &packet)?;
+    // debsigs signs debian-binary, control.tar.* and data.tar.* concatenated in archive order
+    let mut signed = Vec::new();
+    for m in members.iter().filter(|m| !m.name.starts_with('_')) {
+        let mut part = File::open(path)?;
+        part.seek(SeekFrom::Start(m.offset))?;
+        signed.push(part.take(m.size));
+    }
+    let chained = signed.into_iter().fold(Box::new(std::io::empty()) as Box<dyn Read + Send>, |acc, r| Box::new(acc.chain(r)));
+    gpgv(&sig_file.0, chained, "debsigs signature")
+}
+
+/// Run gpgv against the pkgbridge keyring, feeding the signed data on stdin.
+fn gpgv(sig: &Path, mut data: Box<dyn Read + Send>, method: &'static str) -> Result<SigStatus> {
+    let keyring = config::keys_dir().join("pubring.kbx");
+    let mut child = Command::new("gpgv")
+        .arg("--status-fd").arg("1")
+        .arg("--keyring").arg(&keyring)
+        .arg(sig)
+        .arg("-")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .context("running gpgv (install gnupg to verify signatures)")?;
+    let mut stdin = child.stdin.take().ok_or_else(|| anyhow!("failed to open gpgv stdin"))?;
+    let writer = std::thread::spawn(move || std::io::copy(&mut data, &mut stdin));
+    let out = child.wait_with_output()?;
+    // gpgv may stop reading early on a malformed signature; that is reported via status lines
+    let _ = writer.join();
+    let status = String::from_utf8_lossy(&out.stdout);
+    Ok(parse_status(&status, method))
+}
+
+/// Only GOODSIG counts as good; gpgv also reports VALIDSIG for expired or revoked keys.
+fn parse_status(status: &str, method: &'static str) -> SigStatus {
+    let field = |prefix: &str| status.lines().find_map(|l| l.strip_prefix(prefix)).map(|rest| rest.split_whitespace().next().unwrap_or("").to_string());
+    let has = |prefix: &str| status.lines().any(|l| l.starts_with(prefix));
+    if has("[GNUPG:] BADSIG ") { return SigStatus::Bad { method }; }
+    if has("[GNUPG:] REVKEYSIG ") { return SigStatus::Rejected { reason: "signed by a revoked key", method }; }
+    if has("[GNUPG:] EXPKEYSIG ") { return SigStatus::Rejected { reason: "signed by an expired key", method }; }
+    if has("[GNUPG:] EXPSIG ") { return SigStatus::Rejected { reason: "the signature has expired", method }; }
+    if has("[GNUPG:] GOODSIG ") {
+        if let Some(fpr) = field("[GNUPG:] VALIDSIG ") { return SigStatus::Good { fingerprint: fpr, method }; }
+    }
+    if let Some(id) = field("[GNUPG:] NO_PUBKEY ").or_else(|| field("[GNUPG:] ERRSIG ")) {
+        return SigStatus::UnknownKey { key_id: id, method };
+    }
+    SigStatus::Bad { method }
+}
+
+/// Hex digest of everything left in `r`.
+fn hex_digest<D: sha2::Digest>(r: &mut impl Read) -> Result<String> {
+    let mut hasher = D::new();
+    let mut buf = vec![0u8; 1 << 20];
+    loop {
+        let n = r.read(&mut buf)?;
+        if n == 0 { break; }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Scratch file in its own freshly created private directory; both are removed on drop.
+struct TempFile(PathBuf);
+
+impl TempFile {
+    fn write(name: &str, data: &[u8]) -> Result<TempFile> {
+        let path = private_temp_dir()?.join(name);
+        let mut f = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .with_context(|| format!("writing {}", path.display()))?;
+        f.write_all(data)?;
+        Ok(TempFile(path))
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+        if let Some(dir) = self.0.parent() { let _ = fs::remove_dir(dir); }
+    }
+}
+
+/// A new 0700 directory under the temp dir. mkdir fails on any existing entry (symlinks
+/// included), so a name planted by another user is skipped rather than followed.
+fn private_temp_dir() -> Result<PathBuf> {
+    let base = std::env::temp_dir();
+    for attempt in 0..16u32 {
+        let nanos = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.subsec_nanos()).unwrap_or(0);
+        let dir = base.join(format!("pkgbridge-{}-{}-{}", std::process::id(), nanos, attempt));
+        let mut b = fs::DirBuilder::new();
+        #[cfg(unix)] {
+            use std::os::unix::fs::DirBuilderExt;
+            b.mode(0o700);
+        }
+        match b.create(&dir) {
+            Ok(()) => return Ok(dir),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e).with_context(|| format!("creating {}", dir.display())),
+        }
+    }
+    Err(anyhow!("could not create a private directory in {}", base.display()))
+}
+
+// ---- keyring management ----
+
+fn gpg() -> Result<Command> {
+    let dir = config::keys_dir();
+    if !dir.is_dir() {
+        dryrun::create_dir_all(&dir).with_context(|| format!("creating {}", dir.display()))?;
+        #[cfg(unix)] {
+            use std::os::unix::fs::PermissionsExt;
+            dryrun::set_permissions(&dir, fs::Permissions::from_mode(0o700))?;
+        }
+    }
+    let mut c = Command::new("gpg");
+    c.arg("--homedir").arg(&dir).arg("--batch").arg("--no-tty");
+    Ok(c)
+}
+
+/// Import trusted keys from an armored or binary key file.
+pub fn add_key(file: &Path) -> Result<()> {
+    let mut c = gpg()?;
+    c.arg("--import").arg(file);
+    if !dryrun::perform(format_args!("run {}", dryrun::describe(&c))) { return Ok(()); }
+    let out = c.output().context("running gpg --import")?;
+    if !out.status.success() {
+        return Err(anyhow!("importing {} failed: {}", file.display(), String::from_utf8_lossy(&out.stderr).trim()));
+    }
+    // gpg reports what it imported on stderr
+    for line in String::from_utf8_lossy(&out.stderr).lines().filter(|l| l.contains("key ")) {
+        println!("{}", line.trim_start_matches("gpg: "));
+    }
+    Ok(())
+}
+
+/// Trusted keys as (fingerprint, first user id).
+pub fn list_keys() -> Result<Vec<(String, String)>> {
+    let out = gpg()?.args(["--with-colons", "--list-keys"]).output().context("running gpg --list-keys")?;
+    let mut keys: Vec<(String, String)> = Vec::new();
+    let mut in_primary = false;
+    for line in String::from_utf8_lossy(&out.stdout).lines() {
+        let cols: Vec<&str> = line.split(':').collect();
+        match cols.first().copied() {
+            Some("pub") => {
+                keys.push((String::new(), String::new()));
+                in_primary = true;
+            }
+            Some("sub") => in_primary = false,
+            Some("fpr") if in_primary => {
+                if let Some(k) = keys.last_mut() { if k.0.is_empty() { k.0 = cols.get(9).unwrap_or(&"").to_string(); } }
+            }
+            Some("uid") => {
+                if let Some(k) = keys.last_mut() { if k.1.is_empty() { k.1 = cols.get(9).unwrap_or(&"").to_string(); } }
+            }
+            _ => {}
+        }
+    }
+    Ok(keys)
+}
+
+pub fn remove_key(fingerprint: &str) -> Result<()> {
+    let mut c = gpg()?;
+    c.args(["--yes", "--delete-keys", fingerprint]);
+    if !dryrun::perform(format_args!("run {}", dryrun::describe(&c))) { return Ok(()); }
+    let out = c.output().context("running gpg --delete-keys")?;
+    if !out.status.success() {
+        return Err(anyhow!("removing key {} failed: {}", fingerprint, String::from_utf8_lossy(&out.stderr).trim()));
+    }
+    Ok(())
+}
+
+// ---- checksums ----
+
+/// Hex SHA-256 of a file, read in fixed-size chunks.
+pub fn sha256_file(path: &Path) -> Result<String> {
+    let mut f = File::open(path).with_context(|| format!("opening {}", path.display()))?;
+    hex_digest::<sha2::Sha256>(&mut f).with_context(|| format!("reading {}", path.display()))
+}
+
+/// Expected checksum for a package: the `--sha256` value, else its entry in a `SHA256SUMS`
+/// file next to it. Returns the lowercase digest and where it came from.
+pub fn expected_sha256(path: &Path, given: Option<&str>) -> Result<Option<(String, String)>> {
+    if let Some(g) = given {
+        let g = g.trim().to_ascii_lowercase();
+        if g.len() != 64 || !g.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(anyhow!("--sha256 expects 64 hex characters, got '{}'", g));
+        }
+        return Ok(Some((g, "--sha256".to_string())));
+    }
+    let Some(name) = path.file_name().and_then(|n| n.to_str()) else { return Ok(None); };
+    let sums = path.parent().unwrap_or(Path::new(".")).join("SHA256SUMS");
+    let Ok(content) = fs::read_to_string(&sums) else { return Ok(None); };
+    // "<digest>  <name>" or "<digest> *<name>" (binary mode)
+    let found = content.lines().find_map(|l| {
+        let (digest, file) = l.trim().split_once(char::is_whitespace)?;
+        (file.trim_start().trim_start_matches('*') == name).then(|| digest.to_ascii_lowercase())
+    });
+    Ok(found.map(|d| (d, sums.display().to_string())))
+}